@@ -59,7 +59,8 @@ chrome.downloads.onCreated.addListener(function (downloadItem) {
             downloadUrl: downloadItem.finalUrl,
             resumeState: downloadItem.canResume,
             downloadCookie: cookies,
-            downloadReferer: referer
+            downloadReferer: referer,
+            mime: downloadItem.mime || ''
         };
     
         if (devMode) {
@@ -0,0 +1,194 @@
+use std::{fmt::Display, fs, path::{Path, PathBuf}, process::Command};
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+pub enum PostAction {
+	// 移动到目标文件夹
+	Move(String),
+	// 解压到目标文件夹，为空则解压到文件所在的文件夹
+	Extract(String),
+	// 支持 {file} {dir} {name} {url} 占位符
+	Command(String),
+}
+
+impl PostAction {
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Self::Move(_) => "Move",
+			Self::Extract(_) => "Extract",
+			Self::Command(_) => "Command",
+		}
+	}
+
+	pub fn get_value_mut(&mut self) -> &mut String {
+		match self {
+			Self::Move(v) | Self::Extract(v) | Self::Command(v) => v,
+		}
+	}
+
+	pub fn get_hint(&self) -> &'static str {
+		match self {
+			Self::Move(_) => "Target Folder",
+			Self::Extract(_) => "Target Folder (empty for same folder)",
+			Self::Command(_) => "e.g. shasum {file} > {file}.sha",
+		}
+	}
+
+	pub fn all() -> Vec<Self> {
+		vec![
+			Self::Move(String::new()),
+			Self::Extract(String::new()),
+			Self::Command(String::new()),
+		]
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionResult {
+	pub action: String,
+	pub success: bool,
+	pub exit_code: Option<i32>,
+	pub output: String,
+}
+
+impl Display for ActionResult {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let state = if self.success { "OK" } else { "Failed" };
+		match self.exit_code {
+			Some(code) => write!(f, "{} [{} {}] {}", self.action, state, code, self.output),
+			None => write!(f, "{} [{}] {}", self.action, state, self.output),
+		}
+	}
+}
+
+fn expand_home(path: &str) -> PathBuf {
+	let path = path.trim();
+	match (path.strip_prefix("~/"), std::env::var("HOME")) {
+		(Some(rest), Ok(home)) => Path::new(&home).join(rest),
+		_ => PathBuf::from(path),
+	}
+}
+
+fn shell_quote(s: &str) -> String {
+	format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn command_result(action: String, command: &mut Command) -> ActionResult {
+	match command.output() {
+		Ok(output) => {
+			let mut text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+			let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+			if !stderr.is_empty() {
+				if !text.is_empty() {
+					text.push('\n');
+				}
+				text.push_str(&stderr);
+			}
+			ActionResult {
+				action,
+				success: output.status.success(),
+				exit_code: output.status.code(),
+				output: text,
+			}
+		},
+		Err(e) => ActionResult {
+			action,
+			success: false,
+			exit_code: None,
+			output: e.to_string(),
+		},
+	}
+}
+
+fn move_file(file: &Path, target: &str) -> Result<PathBuf, String> {
+	let target_dir = expand_home(target);
+	fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
+	let file_name = file.file_name().ok_or("Invalid file path".to_string())?;
+	let target_file = target_dir.join(file_name);
+	// 跨文件系统时无法直接重命名
+	if fs::rename(file, &target_file).is_err() {
+		fs::copy(file, &target_file).map_err(|e| e.to_string())?;
+		fs::remove_file(file).map_err(|e| e.to_string())?;
+	}
+	Ok(target_file)
+}
+
+fn extract_file(file: &Path, target: &str) -> ActionResult {
+	let action = "Extract".to_string();
+	let target_dir = if target.trim().is_empty() {
+		file.parent().map(|p| p.to_path_buf()).unwrap_or_default()
+	} else {
+		expand_home(target)
+	};
+	if let Err(e) = fs::create_dir_all(&target_dir) {
+		return ActionResult { action, success: false, exit_code: None, output: e.to_string() };
+	}
+	let name = file.to_string_lossy().to_lowercase();
+	let mut command = if name.ends_with(".zip") {
+		let mut c = Command::new("unzip");
+		c.arg("-o").arg(file).arg("-d").arg(&target_dir);
+		c
+	} else {
+		let flag = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+			"-xzf"
+		} else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+			"-xjf"
+		} else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+			"-xJf"
+		} else if name.ends_with(".tar") {
+			"-xf"
+		} else {
+			return ActionResult { action, success: false, exit_code: None, output: "Unsupported archive format".to_string() };
+		};
+		let mut c = Command::new("tar");
+		c.arg(flag).arg(file).arg("-C").arg(&target_dir);
+		c
+	};
+	let mut result = command_result(action, &mut command);
+	if result.success {
+		result.output = target_dir.to_string_lossy().to_string();
+	}
+	result
+}
+
+// 依次执行，移动后的文件路径会传给后续的操作
+pub fn run_actions(actions: &[PostAction], file: &str, url: &str, name: &str) -> Vec<ActionResult> {
+	let mut results = vec![];
+	let mut file = PathBuf::from(file);
+	for action in actions {
+		let result = match action {
+			PostAction::Move(target) => match move_file(&file, target) {
+				Ok(target_file) => {
+					file = target_file;
+					ActionResult {
+						action: "Move".to_string(),
+						success: true,
+						exit_code: None,
+						output: file.to_string_lossy().to_string(),
+					}
+				},
+				Err(e) => ActionResult { action: "Move".to_string(), success: false, exit_code: None, output: e },
+			},
+			PostAction::Extract(target) => extract_file(&file, target),
+			PostAction::Command(command) => {
+				let dir = file.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+				let command = command
+					.replace("{file}", &shell_quote(&file.to_string_lossy()))
+					.replace("{dir}", &shell_quote(&dir))
+					.replace("{name}", &shell_quote(name))
+					.replace("{url}", &shell_quote(url));
+				command_result("Command".to_string(), Command::new("sh").arg("-c").arg(command))
+			},
+		};
+		let success = result.success;
+		results.push(result);
+		// 前一步失败时不再继续
+		if !success {
+			break;
+		}
+	}
+	results
+}
@@ -1,9 +1,9 @@
-use std::{collections::HashMap, time::{Duration, Instant}};
+use std::{collections::HashMap, fs, path::PathBuf, time::{Duration, Instant}};
 
 use aria2_ws::Client;
-use eframe::{App, egui::{CentralPanel, CollapsingHeader, DragValue, Grid, Id, ProgressBar, ScrollArea, TextEdit, TopBottomPanel}};
+use eframe::{App, egui::{CentralPanel, CollapsingHeader, DragValue, Grid, Id, ProgressBar, ScrollArea, ComboBox, TextEdit, TopBottomPanel, Window}, epaint::Color32};
 use futures::executor::block_on;
-use crate::{aria2c::{self, SERVER_URL}, data::{clear_wait_to_start, get_focus_request, get_global_fonts, get_global_style, get_quit_request, get_settings, get_settings_update, get_status_info, get_visual_dark, get_wait_to_start, set_focus_request, set_settings, set_settings_update, set_status_info, set_visual_dark}, history::History, server::Info, session::Session, settings::Settings, widgets::click_copy_label};
+use crate::{aria2c::{self, QueueMove, SERVER_URL}, bandwidth::{BandwidthRule, WEEKDAYS}, batch::{merge_options, parse_batch}, data::{clear_status_log, clear_wait_to_start, get_status_log, get_bandwidth_limit, get_bandwidth_update, get_focus_request, get_global_fonts, get_global_style, get_quit_request, get_settings, get_settings_update, get_status_info, get_visual_dark, get_global_status, get_wait_to_start, set_bandwidth_update, set_session_entries, set_global_status, set_focus_request, set_settings, set_settings_update, set_status_error, set_status_info, set_status_warning, set_visual_dark}, format::{format_duration, format_size, format_speed}, category::{match_category, Category}, cookies::CookieJar, credentials::{delete_password, store_password, strip_credentials, Credential}, duplicate::{unique_name, Duplicate, DuplicatePolicy}, export::{self, collect_entries, import_json, ExportEntry, ExportFormat}, history::History, logger, notification::NotificationMode, options::SessionOptions, proxy::{self, ProxyMode}, scheduler::{Scheduler, StartCondition}, server::Info, session::Session, settings::{get_download_dir, Settings}, stats::{get_available_space, GlobalStatus, SpeedHistory}, status::StatusLevel, site_rules::SiteRule, widgets::{category_editor, click_copy_label, comma_list_edit, piece_map, session_options_grid, site_rule_editor, speed_graph}};
 
 pub struct DownloadManager {
 	sessions: HashMap<String, Session>,
@@ -17,6 +17,38 @@ pub struct DownloadManager {
 	client: Option<Client>,
 	tell_active_time: Instant,
 	startup_time: Instant,
+	show_advanced: bool,
+	advanced_url_input: String,
+	advanced_options: SessionOptions,
+	option_drafts: HashMap<String, SessionOptions>,
+	// 应用设置时才从钥匙串中删除
+	removed_credentials: Vec<Credential>,
+	queue: Vec<String>,
+	scheduler: Scheduler,
+	advanced_condition: StartCondition,
+	advanced_start_time: (u32, u32),
+	global_download_history: SpeedHistory,
+	global_upload_history: SpeedHistory,
+	log_levels: Vec<StatusLevel>,
+	log_filter: String,
+	category_filter: Option<String>,
+	cookie_jar: CookieJar,
+	cookie_import_path: String,
+	wait_to_confirm: Vec<(Session, String)>,
+	skip_confirm_site: bool,
+	wait_to_resolve: Vec<(Session, Duplicate, bool)>,
+	wait_to_probe: Vec<(Session, bool)>,
+	show_batch: bool,
+	batch_input: String,
+	batch_path: String,
+	batch_options: SessionOptions,
+	batch_count: usize,
+	show_export: bool,
+	export_format: ExportFormat,
+	export_sessions: bool,
+	export_history: bool,
+	export_path: String,
+	import_path: String,
 }
 
 impl DownloadManager {
@@ -27,13 +59,21 @@ impl DownloadManager {
 		cc.egui_ctx.set_fonts(get_global_fonts());
 		// 加载样式
 		cc.egui_ctx.set_style(get_global_style());
+		// 恢复计划中的任务
+		for session in obj.scheduler.get_sessions(None) {
+			obj.sessions.insert(session.get_uid(), session);
+		}
 		// 更新连接
 		obj.update_client();
 		// 返回
 		obj
 	}
 
-	fn new_session(&mut self, data: Info) {
+	fn new_session(&mut self, data: Info, confirm: bool) {
+		self.create_session(data, confirm, self.settings.probe_file_info);
+	}
+
+	fn create_session(&mut self, data: Info, confirm: bool, probe: bool) {
 		let url = data.download_url.clone().trim().to_string();
 		if !url.is_empty() {
 			let mut session = match Session::new(url.clone(), self.client.clone()) {
@@ -43,30 +83,359 @@ impl DownloadManager {
 			session.set_cookie(data.download_cookie);
 			session.set_referer(data.download_referer);
 			session.set_webpage(data.webpage_url);
-			session.start();
+			session.set_options(data.options);
+			if data.size > 0 {
+				session.set_size(Some(data.size as u64));
+			}
+			session.set_mime(data.mime);
+			session.set_start_condition(data.start_condition);
+			if probe && session.start_probe() {
+				// 探测完成后再继续添加
+				set_status_info(format!("Probe `{}`", session.get_name()));
+				self.wait_to_probe.push((session, confirm));
+			} else {
+				self.prepare_session(session, confirm);
+			}
+		} else {
+			set_status_warning("Target url cannot be empty".to_string());
+		}
+	}
+
+	fn update_probing(&mut self) {
+		let (finished, probing): (Vec<_>, Vec<_>) = std::mem::take(&mut self.wait_to_probe).into_iter()
+			.map(|(mut session, confirm)| (session.poll_probe(), session, confirm))
+			.partition(|(finished, _, _)| *finished);
+		self.wait_to_probe = probing.into_iter().map(|(_, session, confirm)| (session, confirm)).collect();
+		for (_, session, confirm) in finished {
+			self.prepare_session(session, confirm);
+		}
+	}
+
+	fn prepare_session(&mut self, mut session: Session, confirm: bool) {
+		let mime = session.get_mime();
+		self.apply_category(&mut session, &mime);
+		if let Some(duplicate) = self.find_duplicate(&session) {
+			match self.settings.duplicate_policy {
+				DuplicatePolicy::Ask => {
+					self.wait_to_resolve.push((session, duplicate, confirm));
+					return;
+				},
+				policy => match self.resolve_duplicate(session, duplicate, policy) {
+					Some(s) => session = s,
+					None => return,
+				},
+			}
+		}
+		self.confirm_session(session, confirm);
+	}
+
+	fn confirm_session(&mut self, session: Session, confirm: bool) {
+		if confirm && self.should_confirm(&session) {
+			// 等待用户确认，文件名只在修改后才写入 out
 			let name = session.get_name();
-			self.sessions.insert(session.get_uid(), session);
-			set_status_info(format!("New session to `{}`", name));
+			self.wait_to_confirm.push((session, name));
 		} else {
-			set_status_info("Target url cannot be empty".to_string());
+			self.start_session(session);
+		}
+	}
+
+	// 查找相同地址或相同目标文件的任务
+	fn find_duplicate(&self, session: &Session) -> Option<Duplicate> {
+		let url = strip_credentials(&session.get_url());
+		let target = session.get_target_path();
+		for (uid, s) in self.sessions.iter() {
+			if strip_credentials(&s.get_url()) == url {
+				return Some(Duplicate { uid: Some(uid.clone()), reason: format!("Same url as session `{}`", s.get_name()) });
+			}
+			if s.get_target_path() == target {
+				return Some(Duplicate { uid: Some(uid.clone()), reason: format!("Same file as session `{}`", s.get_name()) });
+			}
+		}
+		for (uid, h) in self.history_sessions.get_sessions() {
+			if self.sessions.contains_key(&uid) {
+				continue;
+			}
+			if h.get_url() == url {
+				return Some(Duplicate { uid: None, reason: format!("Downloaded at {}", h.get_time()) });
+			}
+		}
+		if target.exists() {
+			return Some(Duplicate { uid: None, reason: format!("File `{}` exists", target.display()) });
+		}
+		None
+	}
+
+	// 返回需要继续添加的任务
+	fn resolve_duplicate(&mut self, mut session: Session, duplicate: Duplicate, policy: DuplicatePolicy) -> Option<Session> {
+		let mut options = session.get_options();
+		match policy {
+			DuplicatePolicy::Ask | DuplicatePolicy::Skip => {
+				set_status_info(format!("Skip duplicate download `{}`", session.get_name()));
+				return None;
+			},
+			DuplicatePolicy::Resume => {
+				if let Some(existing) = duplicate.uid.and_then(|uid| self.sessions.get_mut(&uid)) {
+					if existing.is_error() {
+						existing.start();
+					} else if existing.is_paused() {
+						existing.unpause();
+					} else {
+						set_status_info(format!("`{}` is already in the list", existing.get_name()));
+					}
+					return None;
+				}
+				options.resume = true;
+			},
+			DuplicatePolicy::Overwrite => {
+				if let Some(existing) = duplicate.uid.and_then(|uid| self.sessions.get(&uid)) {
+					self.wait_to_remove.push(existing.clone());
+				}
+				options.allow_overwrite = true;
+			},
+			DuplicatePolicy::Rename => {
+				let target = session.get_target_path();
+				let dir = target.parent().map(|p| p.to_path_buf()).unwrap_or(get_download_dir());
+				let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or(session.get_name());
+				let taken: Vec<PathBuf> = self.sessions.values().map(|s| s.get_target_path()).collect();
+				options.out = unique_name(&dir, &name, &taken);
+			},
+		}
+		session.set_options(options);
+		Some(session)
+	}
+
+	fn start_session(&mut self, mut session: Session) {
+		let name = session.get_name();
+		// 检查目标磁盘的剩余空间
+		if !session.is_deferred() && !session.has_enough_space(self.settings.get_min_free_space()) {
+			if self.settings.queue_when_low_space {
+				set_status_warning(format!("Not enough disk space for `{}`, wait until space is available", name));
+				session.set_start_condition(StartCondition::WhenSpace);
+			} else {
+				set_status_warning(format!("Not enough disk space for `{}`", name));
+			}
+		}
+		if session.is_deferred() {
+			self.scheduler.add_session(&session);
+			set_status_info(format!("Schedule `{}` to start {}", name, session.get_start_condition().get_time()));
+		} else {
+			session.start();
+			set_status_info(format!("New session to `{}`", name));
+		}
+		self.sessions.insert(session.get_uid(), session);
+	}
+
+	fn should_confirm(&self, session: &Session) -> bool {
+		if !self.settings.confirm_new_downloads {
+			return false;
+		}
+		let host = session.get_host();
+		!self.settings.skip_confirm_hosts.iter().any(|h| h.trim().eq_ignore_ascii_case(&host))
+	}
+
+	// 不再对该站点询问
+	fn skip_confirm_host(&mut self, host: String) {
+		if host.is_empty() {
+			return;
+		}
+		let mut settings = get_settings();
+		if !settings.skip_confirm_hosts.contains(&host) {
+			settings.skip_confirm_hosts.push(host);
+		}
+		settings.save();
+		self.settings.skip_confirm_hosts = settings.skip_confirm_hosts.clone();
+		set_settings(settings);
+	}
+
+	// 根据分类设置下载文件夹以及完成后操作，单个任务的设置优先
+	fn apply_category(&self, session: &mut Session, mime: &str) {
+		if let Some(category) = match_category(&self.settings.categories, &session.get_url(), &session.get_name(), mime) {
+			let mut options = session.get_options();
+			if options.dir.trim().is_empty() {
+				options.dir = category.get_dir(self.settings.category_subfolders);
+			}
+			options.post_actions.extend(category.post_actions.clone());
+			session.set_options(options);
+			session.set_category(category.name);
+		}
+	}
+
+	fn match_category_filter(&self, category: String) -> bool {
+		match &self.category_filter {
+			Some(filter) => *filter == category,
+			None => true,
 		}
 	}
 
 	fn apply_settings(&mut self) {
+		// 删除已移除的凭据，重新添加的不删除
+		for credential in self.removed_credentials.drain(..) {
+			let (host, user) = (credential.host.trim(), credential.user.trim());
+			if !self.settings.credentials.iter().any(|c| c.host.trim() == host && c.user.trim() == user) {
+				delete_password(host, user);
+			}
+		}
+		// 密码保存到系统钥匙串中
+		for credential in self.settings.credentials.iter_mut() {
+			if credential.password.is_empty() {
+				continue;
+			}
+			match store_password(credential.host.trim(), credential.user.trim(), &credential.password) {
+				Ok(_) => {
+					set_status_info(format!("Save password for `{}`", credential.host));
+					credential.password.clear();
+				},
+				// 保存失败时保留输入，方便重试
+				Err(msg) => set_status_error(format!("Failed to save password for `{}`: {}", credential.host, msg)),
+			}
+		}
 		// 同步设置
 		set_settings(self.settings.clone());
 		// 保存设置
 		self.settings.save();
+		// 更新日志过滤
+		logger::set_filter(&self.settings.log_filter);
+		// 重新加载 PAC 脚本
+		if self.settings.proxy_mode == ProxyMode::Pac {
+			proxy::reload_pac();
+		}
 		// 标记改变
 		self.settings_changed = true;
+		// 应用并发数
+		aria2c::change_max_concurrent_downloads(&self.client, self.settings.max_concurrent_downloads);
 		// 提示信息
 		set_status_info("Apply Settings".to_string());
 	}
 
+	fn update_scheduled(&mut self) {
+		let mut idle = !self.sessions.values().any(|s| s.is_active() || s.is_queued());
+		for session in self.sessions.values_mut() {
+			if session.should_start(idle) {
+				// 空闲时每次只启动一个任务
+				if session.get_start_condition() == StartCondition::WhenIdle {
+					idle = false;
+				}
+				session.start();
+			}
+		}
+	}
+
+	// 启动或重试后 uid 会变为新的 gid，将任务以及相关的记录移动到新的 uid 下
+	fn rekey_sessions(&mut self) {
+		let changed: Vec<String> = self.sessions.iter()
+			.filter(|(uid, session)| **uid != session.get_uid())
+			.map(|(uid, _)| uid.clone())
+			.collect();
+		for uid in changed {
+			let session = self.sessions.remove(&uid).unwrap();
+			let new_uid = session.get_uid();
+			// 已经启动的任务不再需要计划
+			self.scheduler.remove(&uid);
+			if let Some(draft) = self.option_drafts.remove(&uid) {
+				self.option_drafts.insert(new_uid.clone(), draft);
+			}
+			self.history_sessions.rekey(&uid, &new_uid);
+			self.sessions.insert(new_uid, session);
+		}
+	}
+
+	fn get_session_entries(&self) -> Vec<(String, ExportEntry)> {
+		self.sessions.iter()
+			.map(|(uid, s)| (uid.clone(), ExportEntry::from_session(s)))
+			.collect()
+	}
+
+	fn export_entries(&self) {
+		let entries = collect_entries(&self.get_session_entries(), &self.history_sessions, self.export_sessions, self.export_history);
+		let path = if self.export_path.trim().is_empty() {
+			get_download_dir().join(format!("aria-download-manager.{}", self.export_format.get_extension()))
+		} else {
+			PathBuf::from(self.export_path.trim())
+		};
+		match fs::write(&path, export::export(&entries, self.export_format)) {
+			Ok(_) => set_status_info(format!("Export {} entries to `{}`", entries.len(), path.display())),
+			Err(msg) => set_status_error(format!("Failed to export to `{}`: {}", path.display(), msg)),
+		}
+	}
+
+	fn import_entries(&mut self) {
+		let entries = match fs::read_to_string(self.import_path.trim()).map_err(|e| e.to_string()).and_then(|c| import_json(&c)) {
+			Ok(entries) => entries,
+			Err(msg) => {
+				set_status_error(format!("Failed to import `{}`: {}", self.import_path, msg));
+				return;
+			}
+		};
+		let count = entries.len();
+		for entry in entries {
+			self.create_session(entry.to_info(), false, false);
+		}
+		set_status_info(format!("Import {} sessions", count));
+	}
+
+	// 剩余空间低于阈值时暂停所有任务
+	fn check_free_space(&mut self) {
+		let reserve = self.settings.get_min_free_space();
+		if reserve == 0 {
+			return;
+		}
+		let low = self.sessions.values()
+			.filter(|s| s.is_active())
+			.filter_map(|s| s.get_target_path().parent().and_then(get_available_space))
+			.any(|free| free < reserve);
+		if low {
+			for session in self.sessions.values_mut().filter(|s| s.is_active() || s.is_queued()) {
+				session.pause();
+			}
+			set_status_error(format!("Free space is lower than {}, pause all downloads", format_size(reserve)));
+		}
+	}
+
+	// 返回是否所有任务都已完成
+	fn update_sessions(&mut self) -> bool {
+		let mut all_finished = true;
+		for session in self.sessions.values_mut() {
+			session.update_status();
+			session.check_retry(&self.settings.retry_policy);
+			session.check_notification(self.settings.notification_mode);
+			session.check_post_actions();
+			session.check_cookie_file();
+			if !session.is_deferred() {
+				self.history_sessions.add_session(session.clone());
+			}
+			if !session.is_completed() {
+				all_finished = false;
+			}
+		}
+		all_finished
+	}
+
+	// 正在下载的排在最前，然后按照 aria2 的队列顺序排列
+	fn get_sorted_uids(&self) -> Vec<String> {
+		let mut uids: Vec<(usize, usize, String)> = self.sessions.iter()
+			.map(|(uid, session)| {
+				if let Some(position) = self.queue.iter().position(|gid| *gid == session.get_gid()) {
+					(1, position, uid.clone())
+				} else if session.is_deferred() {
+					(2, 0, uid.clone())
+				} else if session.is_completed() {
+					(3, 0, uid.clone())
+				} else {
+					(0, 0, uid.clone())
+				}
+			})
+			.collect();
+		uids.sort();
+		uids.into_iter().map(|(_, _, uid)| uid).collect()
+	}
+
 	fn update_session_client(&mut self) {
 		for (_uid, session) in self.sessions.iter_mut() {
 			session.set_client(self.client.clone());
 		}
+		// 新的连接需要重新应用全局限速以及并发数
+		set_bandwidth_update(true);
+		aria2c::change_max_concurrent_downloads(&self.client, self.settings.max_concurrent_downloads);
 	}
 
 	fn update_client(&mut self) {
@@ -90,7 +459,7 @@ impl DownloadManager {
 				Some(c)
 			},
 			Err(e) => {
-				set_status_info(format!("Connection Error: {:?}", e.to_string()));
+				set_status_error(format!("Connection Error: {:?}", e.to_string()));
 				None
 			}
 		};
@@ -113,6 +482,37 @@ impl Default for DownloadManager {
 			client: None,
 			tell_active_time: Instant::now(),
 			startup_time: Instant::now(),
+			show_advanced: false,
+			advanced_url_input: String::new(),
+			advanced_options: SessionOptions::default(),
+			option_drafts: HashMap::new(),
+			removed_credentials: vec![],
+			queue: vec![],
+			scheduler: Scheduler::new(),
+			advanced_condition: StartCondition::Now,
+			advanced_start_time: (2, 0),
+			global_download_history: SpeedHistory::default(),
+			global_upload_history: SpeedHistory::default(),
+			log_levels: vec![StatusLevel::Info, StatusLevel::Warning, StatusLevel::Error],
+			log_filter: String::new(),
+			category_filter: None,
+			cookie_jar: CookieJar::new(),
+			cookie_import_path: String::new(),
+			wait_to_confirm: vec![],
+			skip_confirm_site: false,
+			wait_to_resolve: vec![],
+			wait_to_probe: vec![],
+			show_batch: false,
+			batch_input: String::new(),
+			batch_path: String::new(),
+			batch_options: SessionOptions::default(),
+			batch_count: 0,
+			show_export: false,
+			export_format: ExportFormat::Json,
+			export_sessions: true,
+			export_history: true,
+			export_path: String::new(),
+			import_path: String::new(),
 		}
 	}
 }
@@ -126,6 +526,7 @@ impl App for DownloadManager {
 			ctx.set_style(get_global_style());
 			self.settings = get_settings();
 			self.settings.save();
+			self.removed_credentials.clear();
 			self.settings_changed = false;
 			// 完成更新请求
 			set_settings_update(false);
@@ -134,19 +535,34 @@ impl App for DownloadManager {
 		if visual_dark != get_visual_dark() {
 			set_visual_dark(visual_dark);
 		}
+		// 应用全局限速
+		if get_bandwidth_update() && self.client.is_some() {
+			aria2c::change_global_limit(&self.client, &get_bandwidth_limit());
+			set_bandwidth_update(false);
+		}
+		// 界面中启动的任务需要先更新 uid，再获取状态或者删除
+		self.rekey_sessions();
 		// 更新 sessions
 		if self.tell_active_time.elapsed().as_secs() > 1 {
 			aria2c::get_active(&self.client, &mut self.sessions);
+			self.queue = aria2c::get_waiting(&self.client);
+			if let Some(stat) = aria2c::get_global_stat(&self.client) {
+				self.global_download_history.push(stat.download_speed);
+				self.global_upload_history.push(stat.upload_speed);
+				set_global_status(GlobalStatus::new(&stat, &get_download_dir()));
+			}
+			set_session_entries(self.get_session_entries());
+			self.check_free_space();
 			self.tell_active_time = Instant::now();
 		}
 		// 判断是否需要退出
 		if get_quit_request() {
-			println!("Quit");
+			log::info!("Quit");
 			frame.close();
 		}
 		// 判断是否超过，等待时间
 		if self.settings.close_after_seconds != 0 && self.startup_time.elapsed().as_secs() > self.settings.close_after_seconds {
-			println!("Time Out Quit");
+			log::info!("Time Out Quit");
 			frame.close();
 		}
 		// 处理内容
@@ -154,19 +570,29 @@ impl App for DownloadManager {
 			for s in self.wait_to_remove.iter_mut() {
 				s.remove();
 				self.sessions.remove(&s.get_uid());
+				self.option_drafts.remove(&s.get_uid());
+				self.scheduler.remove(&s.get_uid());
 			}
 			self.wait_to_remove.clear();
 		}
+		// 启动到达计划时间的任务
+		self.update_scheduled();
 		// 读取待添加的任务
 		let wait_to_start = get_wait_to_start();
 		for u in wait_to_start.iter() {
-			self.new_session(u.clone());
+			self.new_session(u.clone(), true);
 		}
 		clear_wait_to_start();
+		// 处理探测完成的任务
+		self.update_probing();
+		// 启动的任务会改变 uid
+		self.rekey_sessions();
 		// 获取状态栏数据
 		self.info = get_status_info();
-		// 判断是否需要刷新
-		let mut all_finished = true;
+		// 更新所有任务的状态，包括被分类筛选隐藏的任务
+		let all_finished = self.update_sessions();
+		// 重试的任务同样会改变 uid
+		self.rekey_sessions();
 
 		// 绘制 ui
 		TopBottomPanel::top(Id::new("top")).show(ctx, |ui| {
@@ -175,9 +601,33 @@ impl App for DownloadManager {
 				ui.horizontal(|ui| {
 					ui.add(TextEdit::singleline(&mut self.url_input).hint_text("Target Url"));
 					if ui.button("New Session").clicked() {
-						self.new_session(Info::with_download_url(self.url_input.clone()));
+						self.new_session(Info::with_download_url(self.url_input.clone()), false);
+					}
+					if ui.button("Advanced").clicked() {
+						self.advanced_url_input = self.url_input.clone();
+						self.show_advanced = true;
+					}
+					if ui.button("Batch Add").clicked() {
+						self.show_batch = true;
+					}
+					if ui.button("Export / Import").clicked() {
+						self.show_export = true;
 					}
 					ui.checkbox(&mut self.show_history, "Show History");
+					let selected = match &self.category_filter {
+						Some(c) if c.is_empty() => "Uncategorized".to_string(),
+						Some(c) => c.clone(),
+						None => "All Categories".to_string(),
+					};
+					ComboBox::from_id_source("category_filter")
+					.selected_text(selected)
+					.show_ui(ui, |ui| {
+						ui.selectable_value(&mut self.category_filter, None, "All Categories");
+						for category in self.settings.categories.iter() {
+							ui.selectable_value(&mut self.category_filter, Some(category.name.clone()), &category.name);
+						}
+						ui.selectable_value(&mut self.category_filter, Some(String::new()), "Uncategorized");
+					});
 					if ui.button("Reconnect Aria2").clicked() {
 						self.update_client();
 					}
@@ -186,14 +636,253 @@ impl App for DownloadManager {
 			ui.add_space(5.0);
 		});
 
+		let mut show_advanced = self.show_advanced;
+		let mut advanced_start = false;
+		Window::new("Advanced New Session")
+		.open(&mut show_advanced)
+		.collapsible(false)
+		.show(ctx, |ui| {
+			ui.add(TextEdit::singleline(&mut self.advanced_url_input).hint_text("Target Url"));
+			session_options_grid(ui, "advanced_options".to_string(), &mut self.advanced_options);
+			ui.horizontal(|ui| {
+				ui.label("Start");
+				ui.radio_value(&mut self.advanced_condition, StartCondition::Now, "Now");
+				if ui.radio(matches!(self.advanced_condition, StartCondition::At(_)), "At").clicked() {
+					self.advanced_condition = StartCondition::At(0);
+				}
+				ui.add(DragValue::new(&mut self.advanced_start_time.0).clamp_range(0..=23));
+				ui.label(":");
+				ui.add(DragValue::new(&mut self.advanced_start_time.1).clamp_range(0..=59));
+				ui.radio_value(&mut self.advanced_condition, StartCondition::WhenIdle, "When Idle");
+			});
+			ui.horizontal(|ui| {
+				if ui.button("Start").clicked() {
+					advanced_start = true;
+				}
+				if ui.button("Reset").clicked() {
+					self.advanced_options = SessionOptions::default();
+				}
+			});
+		});
+		self.show_advanced = show_advanced;
+		if advanced_start {
+			let mut info = Info::with_download_url(self.advanced_url_input.clone());
+			info.options = self.advanced_options.clone();
+			info.start_condition = match self.advanced_condition {
+				StartCondition::At(_) => StartCondition::next_time(self.advanced_start_time.0, self.advanced_start_time.1),
+				_ => self.advanced_condition.clone(),
+			};
+			self.new_session(info, false);
+			self.show_advanced = false;
+		}
+
+		let mut show_batch = self.show_batch;
+		let mut batch_start = false;
+		Window::new("Batch Add")
+		.open(&mut show_batch)
+		.collapsible(false)
+		.show(ctx, |ui| {
+			ui.horizontal(|ui| {
+				ui.add(TextEdit::singleline(&mut self.batch_path).hint_text("Path to .txt or aria2 input file"));
+				if ui.button("Load").clicked() {
+					match fs::read_to_string(self.batch_path.trim()) {
+						Ok(content) => {
+							self.batch_count = parse_batch(&content).len();
+							self.batch_input = content;
+						},
+						Err(msg) => set_status_error(format!("Failed to read `{}`: {}", self.batch_path, msg)),
+					}
+				}
+			});
+			ScrollArea::vertical().id_source("batch_input").max_height(200.0).show(ui, |ui| {
+				if ui.add(
+					TextEdit::multiline(&mut self.batch_input)
+					.hint_text("One url per line, e.g. https://example.com/file[001-120].jpg")
+					.desired_rows(6)
+				).changed() {
+					self.batch_count = parse_batch(&self.batch_input).len();
+				}
+			});
+			ui.label(format!("{} urls", self.batch_count));
+			session_options_grid(ui, "batch_options".to_string(), &mut self.batch_options);
+			ui.horizontal(|ui| {
+				if ui.button("Add").clicked() {
+					batch_start = true;
+				}
+				if ui.button("Reset").clicked() {
+					self.batch_options = SessionOptions::default();
+				}
+			});
+		});
+		self.show_batch = show_batch;
+		if batch_start {
+			let entries = parse_batch(&self.batch_input);
+			let count = entries.len();
+			for entry in entries {
+				let mut info = Info::with_download_url(entry.url);
+				info.options = merge_options(&self.batch_options, &entry.options);
+				// 批量添加时不探测，避免同时发出大量请求
+				self.create_session(info, false, false);
+			}
+			set_status_info(format!("Add {} sessions", count));
+			self.batch_input.clear();
+			self.batch_count = 0;
+			self.show_batch = false;
+		}
+
+		let mut show_export = self.show_export;
+		let (mut export_clicked, mut import_clicked) = (false, false);
+		Window::new("Export / Import")
+		.open(&mut show_export)
+		.collapsible(false)
+		.show(ctx, |ui| {
+			Grid::new("export")
+			.num_columns(2)
+			.show(ui, |ui| {
+				ui.label("Format");
+				ComboBox::from_id_source("export_format")
+				.selected_text(self.export_format.get_name())
+				.show_ui(ui, |ui| {
+					for format in [ExportFormat::Aria2, ExportFormat::Csv, ExportFormat::Json] {
+						ui.selectable_value(&mut self.export_format, format, format.get_name());
+					}
+				});
+				ui.end_row();
+
+				ui.label("Include");
+				ui.horizontal(|ui| {
+					ui.checkbox(&mut self.export_sessions, "Sessions");
+					ui.checkbox(&mut self.export_history, "History");
+				});
+				ui.end_row();
+
+				ui.label("Export Path");
+				ui.horizontal(|ui| {
+					let hint = get_download_dir().join(format!("aria-download-manager.{}", self.export_format.get_extension()));
+					ui.add(TextEdit::singleline(&mut self.export_path).hint_text(hint.to_string_lossy()));
+					if ui.button("Export").clicked() {
+						export_clicked = true;
+					}
+				});
+				ui.end_row();
+
+				ui.label("Import JSON");
+				ui.horizontal(|ui| {
+					ui.add(TextEdit::singleline(&mut self.import_path).hint_text("Path to exported JSON"));
+					if ui.button("Import").clicked() {
+						import_clicked = true;
+					}
+				});
+				ui.end_row();
+			});
+		});
+		self.show_export = show_export;
+		if export_clicked {
+			self.export_entries();
+		}
+		if import_clicked {
+			self.import_entries();
+		}
+
+		// 处理重复的任务
+		let mut resolve_policy = None;
+		if let Some((session, duplicate, _)) = self.wait_to_resolve.first() {
+			Window::new("Duplicate Download")
+			.collapsible(false)
+			.show(ctx, |ui| {
+				ui.label(format!("`{}` may be a duplicate.", session.get_name()));
+				ui.label(&duplicate.reason);
+				ui.horizontal(|ui| {
+					for policy in [DuplicatePolicy::Skip, DuplicatePolicy::Resume, DuplicatePolicy::Overwrite, DuplicatePolicy::Rename] {
+						if ui.button(policy.get_name()).clicked() {
+							resolve_policy = Some(policy);
+						}
+					}
+				});
+			});
+		}
+		if let Some(policy) = resolve_policy {
+			let (session, duplicate, confirm) = self.wait_to_resolve.remove(0);
+			if let Some(session) = self.resolve_duplicate(session, duplicate, policy) {
+				self.confirm_session(session, confirm);
+			}
+		}
+
+		// 每次只确认一个任务
+		let mut confirm_result = None;
+		if let Some((session, name)) = self.wait_to_confirm.first_mut() {
+			let mut options = session.get_options();
+			Window::new("Confirm Download")
+			.collapsible(false)
+			.show(ctx, |ui| {
+				Grid::new("confirm_download")
+				.num_columns(2)
+				.show(ui, |ui| {
+					ui.label("File Name");
+					ui.text_edit_singleline(name);
+					ui.end_row();
+
+					ui.label("Size");
+					ui.label(session.get_size().map(format_size).unwrap_or("Unknown".to_string()));
+					ui.end_row();
+
+					ui.label("MIME Type");
+					ui.label(if session.get_mime().is_empty() { "Unknown".to_string() } else { session.get_mime() });
+					ui.end_row();
+
+					ui.label("Url");
+					click_copy_label(ui, session.get_url());
+					ui.end_row();
+
+					ui.label("Webpage");
+					click_copy_label(ui, session.get_webpage());
+					ui.end_row();
+
+					ui.label("Download Folder");
+					ui.add(TextEdit::singleline(&mut options.dir).hint_text(get_download_dir().to_string_lossy()));
+					ui.end_row();
+
+					ui.label("Category");
+					ui.label(if session.get_category().is_empty() { "Uncategorized".to_string() } else { session.get_category() });
+					ui.end_row();
+				});
+				ui.checkbox(&mut self.skip_confirm_site, format!("Don't ask for `{}` again", session.get_host()));
+				ui.horizontal(|ui| {
+					if ui.button("Start").clicked() {
+						confirm_result = Some(true);
+					}
+					if ui.button("Cancel").clicked() {
+						confirm_result = Some(false);
+					}
+				});
+			});
+			session.set_options(options);
+		}
+		if let Some(start) = confirm_result {
+			let (mut session, name) = self.wait_to_confirm.remove(0);
+			if name.trim() != session.get_name() && !name.trim().is_empty() {
+				let mut options = session.get_options();
+				options.out = name.trim().to_string();
+				session.set_options(options);
+			}
+			if self.skip_confirm_site {
+				self.skip_confirm_host(session.get_host());
+				self.skip_confirm_site = false;
+			}
+			if start {
+				self.start_session(session);
+			} else {
+				set_status_info(format!("Cancel download `{}`", session.get_name()));
+			}
+		}
+
 		CentralPanel::default().show(ctx, |ui| {
 			ScrollArea::vertical().show(ui, |ui| {
-				for (uid, session) in self.sessions.iter_mut() {
-					session.update_status();
-					self.history_sessions.add_session(session.clone());
-					if !session.is_completed() {
-						all_finished = false;
+				for uid in self.get_sorted_uids() {
+					if !self.match_category_filter(self.sessions[&uid].get_category()) {
+						continue;
 					}
+					let session = self.sessions.get_mut(&uid).unwrap();
 					ui.horizontal(|ui| {
 						if ui.button("Remove").clicked() {
 							self.wait_to_remove.push(session.clone());
@@ -213,11 +902,28 @@ impl App for DownloadManager {
 						if ui.button("Pause").clicked() {
 							session.pause();
 						}
+						if self.queue.contains(&session.get_gid()) {
+							if ui.button("Top").on_hover_text("Move to Top").clicked() {
+								session.move_in_queue(QueueMove::Top);
+							}
+							if ui.button("Up").on_hover_text("Move Up").clicked() {
+								session.move_in_queue(QueueMove::Up);
+							}
+							if ui.button("Down").on_hover_text("Move Down").clicked() {
+								session.move_in_queue(QueueMove::Down);
+							}
+							if ui.button("Bottom").on_hover_text("Move to Bottom").clicked() {
+								session.move_in_queue(QueueMove::Bottom);
+							}
+						}
 						ui.add(
 							ProgressBar::new(session.get_process())
 							.text(session.get_speed())
 						);
 					});
+					if let Some(error) = session.get_error() {
+						ui.colored_label(Color32::RED, format!("{}: {}", error, error.get_suggestion()));
+					}
 					CollapsingHeader::new("Detailed Information")
 					.id_source(uid.to_string() + "detail")
 					.show(ui, |ui| {
@@ -230,6 +936,28 @@ impl App for DownloadManager {
 								click_copy_label(ui, session.get_gid());
 								ui.end_row();
 
+								ui.label("Category");
+								click_copy_label(ui, session.get_category());
+								ui.end_row();
+
+								if let Some(size) = session.get_size() {
+									ui.label("Size");
+									click_copy_label(ui, format_size(size));
+									ui.end_row();
+								}
+
+								if !session.get_mime().is_empty() {
+									ui.label("MIME Type");
+									click_copy_label(ui, session.get_mime());
+									ui.end_row();
+								}
+
+								if session.is_deferred() {
+									ui.label("Scheduled Start");
+									click_copy_label(ui, session.get_start_condition().get_time());
+									ui.end_row();
+								}
+
 								ui.label("Download Url");
 								click_copy_label(ui, session.get_url());
 								ui.end_row();
@@ -250,15 +978,39 @@ impl App for DownloadManager {
 								click_copy_label(ui, session.get_verified_data());
 								ui.end_row();
 
+								ui.label("Elapsed");
+								click_copy_label(ui, format_duration(session.get_elapsed()));
+								ui.end_row();
+
+								ui.label("Average Speed");
+								click_copy_label(ui, format_speed(session.get_average_speed()));
+								ui.end_row();
+
+								ui.label("Remaining Time");
+								click_copy_label(ui, session.get_eta_data());
+								ui.end_row();
+
 								ui.label("Connection Number");
 								click_copy_label(ui, session.get_connections_num().to_string());
 								ui.end_row();
 
 								ui.label("Pieces");
-								click_copy_label(ui, format!("{}B * {}", session.get_pieces_length(), session.get_pieces_num()));
+								click_copy_label(ui, format!("{} * {}", format_size(session.get_pieces_length()), session.get_pieces_num()));
+								ui.end_row();
+
+								ui.label("Piece Map");
+								piece_map(ui, &session.get_pieces());
 								ui.end_row();
 
-								if session.is_error() {
+								if let Some(error) = session.get_error() {
+									ui.label("Error");
+									click_copy_label(ui, error.get_description());
+									ui.end_row();
+
+									ui.label("Suggestion");
+									click_copy_label(ui, error.get_suggestion());
+									ui.end_row();
+
 									ui.label("Error Code");
 									click_copy_label(ui, session.get_error_code());
 									ui.end_row();
@@ -267,6 +1019,40 @@ impl App for DownloadManager {
 									click_copy_label(ui, session.get_error_msg());
 									ui.end_row();
 								}
+
+								for (index, attempt) in session.get_retry_attempts().iter().enumerate() {
+									ui.label(format!("Retry {}", index + 1));
+									click_copy_label(ui, attempt);
+									ui.end_row();
+								}
+
+								for (index, result) in session.get_action_results().iter().enumerate() {
+									ui.label(format!("Post Action {}", index + 1));
+									click_copy_label(ui, result);
+									ui.end_row();
+								}
+							});
+						});
+						if !session.get_speed_history().is_empty() {
+							speed_graph(ui, uid.to_string() + "speed", vec![("Download", session.get_speed_history())], 60.0);
+						}
+						CollapsingHeader::new("Options")
+						.id_source(uid.to_string() + "options")
+						.show(ui, |ui| {
+							let draft = self.option_drafts.entry(uid.clone())
+								.or_insert(session.get_options());
+							session_options_grid(ui, uid.to_string() + "options" + "grid", draft);
+							ui.horizontal(|ui| {
+								if ui.button("Apply").clicked() {
+									session.change_options(draft.clone());
+									// 计划中的任务需要更新保存的选项
+									if session.is_deferred() {
+										self.scheduler.add_session(session);
+									}
+								}
+								if ui.button("Reset").clicked() {
+									*draft = session.get_options();
+								}
 							});
 						});
 					});
@@ -275,7 +1061,7 @@ impl App for DownloadManager {
 				// 历史记录
 				if self.show_history {
 					for (uid, session) in self.history_sessions.get_sessions() {
-						if self.sessions.contains_key(&uid) {
+						if self.sessions.contains_key(&uid) || !self.match_category_filter(session.get_category()) {
 							continue;
 						}
 						ui.horizontal(|ui| {
@@ -313,6 +1099,18 @@ impl App for DownloadManager {
 									ui.label("Start Time");
 									click_copy_label(ui, session.get_time());
 									ui.end_row();
+
+									if let Some(error) = session.get_error() {
+										ui.label("Error");
+										click_copy_label(ui, error);
+										ui.end_row();
+									}
+
+									for (index, result) in session.get_action_results().iter().enumerate() {
+										ui.label(format!("Post Action {}", index + 1));
+										click_copy_label(ui, result);
+										ui.end_row();
+									}
 								});
 							});
 						});
@@ -325,9 +1123,70 @@ impl App for DownloadManager {
 
 		TopBottomPanel::bottom(Id::new("bottom")).show(ctx, |ui| {
 			ui.add_space(5.0);
+			if let Some(status) = get_global_status() {
+				ui.horizontal(|ui| {
+					ui.label(format!("↓ {} ↑ {}", format_speed(status.download_speed), format_speed(status.upload_speed)));
+					ui.separator();
+					ui.label(format!("Active {} Waiting {} Stopped {}", status.num_active, status.num_waiting, status.num_stopped));
+					ui.separator();
+					let free_space = match status.free_space {
+						Some(free_space) => format_size(free_space),
+						None => "Unknown".to_string(),
+					};
+					ui.label(format!("Free {}", free_space))
+					.on_hover_text(&status.download_dir);
+				});
+			}
 			ui.horizontal(|ui| {
+				let limit = get_bandwidth_limit();
+				ui.label(format!("↓ {} ↑ {}", limit.get_download(), limit.get_upload()))
+				.on_hover_text("Current Bandwidth Limit");
+				ui.separator();
 				ui.label(&self.info);
 			});
+			ui.collapsing("Log", |ui| {
+				ui.horizontal(|ui| {
+					for level in [StatusLevel::Info, StatusLevel::Warning, StatusLevel::Error] {
+						let mut checked = self.log_levels.contains(&level);
+						if ui.checkbox(&mut checked, level.to_string()).changed() {
+							if checked {
+								self.log_levels.push(level);
+							} else {
+								self.log_levels.retain(|l| *l != level);
+							}
+						}
+					}
+					ui.add(TextEdit::singleline(&mut self.log_filter).hint_text("Filter"));
+				});
+				let filter = self.log_filter.to_lowercase();
+				let entries: Vec<String> = get_status_log().iter()
+					.filter(|e| self.log_levels.contains(&e.level))
+					.filter(|e| filter.is_empty() || e.message.to_lowercase().contains(&filter))
+					.map(|e| e.to_string())
+					.collect();
+				ScrollArea::vertical().id_source("log").max_height(120.0).stick_to_bottom(true)
+				.show(ui, |ui| {
+					for entry in entries.iter() {
+						ui.label(entry);
+					}
+				});
+				ui.horizontal(|ui| {
+					if ui.button("Copy").clicked() {
+						ui.output_mut(|p| {
+							p.copied_text = entries.join("\n");
+						});
+					}
+					if ui.button("Clear").clicked() {
+						clear_status_log();
+					}
+				});
+			});
+			ui.collapsing("Speed Graph", |ui| {
+				speed_graph(ui, "global_speed".to_string(), vec![
+					("Download", &self.global_download_history),
+					("Upload", &self.global_upload_history),
+				], 100.0);
+			});
 			ui.collapsing("Settings", |ui| {
 				ScrollArea::vertical().show(ui, |ui| {
 					Grid::new("settings")
@@ -337,14 +1196,56 @@ impl App for DownloadManager {
 						ui.add(DragValue::new(&mut self.settings.split_num).clamp_range(1..=64));
 						ui.end_row();
 
-						ui.label("All Proxy Url");
-						ui.text_edit_singleline(&mut self.settings.proxy);
+						ui.label("Max Concurrent Downloads");
+						ui.add(DragValue::new(&mut self.settings.max_concurrent_downloads).clamp_range(1..=64));
+						ui.end_row();
+
+						ui.label("Proxy Mode");
+						ComboBox::from_id_source("proxy_mode")
+						.selected_text(self.settings.proxy_mode.get_name())
+						.show_ui(ui, |ui| {
+							for mode in [ProxyMode::None, ProxyMode::Manual, ProxyMode::Environment, ProxyMode::Pac] {
+								ui.selectable_value(&mut self.settings.proxy_mode, mode, mode.get_name());
+							}
+						});
 						ui.end_row();
 
+						match self.settings.proxy_mode {
+							ProxyMode::Manual => {
+								ui.label("All Proxy Url");
+								ui.text_edit_singleline(&mut self.settings.proxy);
+								ui.end_row();
+							},
+							ProxyMode::Pac => {
+								ui.label("PAC Url");
+								ui.add(TextEdit::singleline(&mut self.settings.pac_url).hint_text("http://... or file path"));
+								ui.end_row();
+							},
+							_ => {},
+						}
+
+						if matches!(self.settings.proxy_mode, ProxyMode::Manual | ProxyMode::Pac) {
+							ui.label("No Proxy");
+							ui.add(TextEdit::singleline(&mut self.settings.no_proxy).hint_text("localhost, .example.com"));
+							ui.end_row();
+						}
+
 						ui.label("User Agent");
 						ui.text_edit_singleline(&mut self.settings.user_agent);
 						ui.end_row();
 
+						ui.label("Max Download Limit");
+						ui.add(TextEdit::singleline(&mut self.settings.max_download_limit).hint_text("e.g. 500K, 2M"));
+						ui.end_row();
+
+						ui.label("Max Upload Limit");
+						ui.add(TextEdit::singleline(&mut self.settings.max_upload_limit).hint_text("e.g. 500K, 2M"));
+						ui.end_row();
+
+						ui.label("Bandwidth Schedule");
+						ui.checkbox(&mut self.settings.enable_bandwidth_schedule, "Enable");
+						ui.end_row();
+
 						ui.label("Close after Seconds");
 						ui.horizontal(|ui| {
 							ui.add(DragValue::new(&mut self.settings.close_after_seconds).clamp_range(0..=3600));
@@ -352,6 +1253,86 @@ impl App for DownloadManager {
 						});
 						ui.end_row();
 
+						ui.label("Auto Retry");
+						ui.checkbox(&mut self.settings.retry_policy.enabled, "Enable");
+						ui.end_row();
+
+						if self.settings.retry_policy.enabled {
+							ui.label("Max Retry Attempts");
+							ui.add(DragValue::new(&mut self.settings.retry_policy.max_attempts).clamp_range(1..=100));
+							ui.end_row();
+
+							ui.label("Retry Delay (s)");
+							ui.horizontal(|ui| {
+								ui.add(DragValue::new(&mut self.settings.retry_policy.initial_delay_seconds).clamp_range(1..=3600));
+								ui.label("x");
+								ui.add(DragValue::new(&mut self.settings.retry_policy.backoff_factor).clamp_range(1.0..=10.0).speed(0.1));
+								ui.label("up to");
+								ui.add(DragValue::new(&mut self.settings.retry_policy.max_delay_seconds).clamp_range(1..=86400));
+							});
+							ui.end_row();
+
+							ui.label("Retryable Error Codes");
+							let mut codes = self.settings.retry_policy.get_retryable_codes();
+							if ui.add(TextEdit::singleline(&mut codes).hint_text("e.g. 2, 6, 19")).changed() {
+								self.settings.retry_policy.set_retryable_codes(&codes);
+							}
+							ui.end_row();
+						}
+
+						ui.label("Notifications");
+						ComboBox::from_id_source("notification_mode")
+						.selected_text(self.settings.notification_mode.get_name())
+						.show_ui(ui, |ui| {
+							for mode in [NotificationMode::All, NotificationMode::FailuresOnly, NotificationMode::Off] {
+								ui.selectable_value(&mut self.settings.notification_mode, mode, mode.get_name());
+							}
+						});
+						ui.end_row();
+
+						ui.label("Log Filter");
+						ui.add(TextEdit::singleline(&mut self.settings.log_filter).hint_text("info,aria_download_manager::server=debug"));
+						ui.end_row();
+
+						ui.label("Confirm New Downloads");
+						ui.checkbox(&mut self.settings.confirm_new_downloads, "Enable");
+						ui.end_row();
+
+						if self.settings.confirm_new_downloads {
+							ui.label("Don't Ask for Sites");
+							comma_list_edit(ui, &mut self.settings.skip_confirm_hosts, "e.g. example.com");
+							ui.end_row();
+						}
+
+						ui.label("Duplicate Downloads");
+						ComboBox::from_id_source("duplicate_policy")
+						.selected_text(self.settings.duplicate_policy.get_name())
+						.show_ui(ui, |ui| {
+							for policy in [DuplicatePolicy::Ask, DuplicatePolicy::Skip, DuplicatePolicy::Resume, DuplicatePolicy::Overwrite, DuplicatePolicy::Rename] {
+								ui.selectable_value(&mut self.settings.duplicate_policy, policy, policy.get_name());
+							}
+						});
+						ui.end_row();
+
+						ui.label("Min Free Space (MB)");
+						ui.horizontal(|ui| {
+							ui.add(DragValue::new(&mut self.settings.min_free_space).clamp_range(0..=1024 * 1024));
+							ui.label("(0 represents no check)");
+						});
+						ui.end_row();
+
+						ui.label("Queue When Low on Space");
+						ui.checkbox(&mut self.settings.queue_when_low_space, "Enable");
+						ui.end_row();
+
+						ui.label("Probe File Info");
+						ui.checkbox(&mut self.settings.probe_file_info, "Enable");
+						ui.end_row();
+
+						ui.label("Save Status Log");
+						ui.checkbox(&mut self.settings.persist_status_log, "Enable");
+						ui.end_row();
+
 						ui.label("Custom Theme");
 						ui.checkbox(&mut self.settings.custom_theme, "Enable");
 						ui.end_row();
@@ -362,6 +1343,125 @@ impl App for DownloadManager {
 							ui.end_row();
 						}
 					});
+					if self.settings.enable_bandwidth_schedule {
+						let mut wait_to_remove_rule = None;
+						for (index, rule) in self.settings.bandwidth_schedule.iter_mut().enumerate() {
+							ui.horizontal(|ui| {
+								for (day, name) in WEEKDAYS.iter().enumerate() {
+									ui.checkbox(&mut rule.days[day], *name);
+								}
+							});
+							ui.horizontal(|ui| {
+								ui.add(DragValue::new(&mut rule.start.0).clamp_range(0..=23));
+								ui.label(":");
+								ui.add(DragValue::new(&mut rule.start.1).clamp_range(0..=59));
+								ui.label("-");
+								ui.add(DragValue::new(&mut rule.end.0).clamp_range(0..=24));
+								ui.label(":");
+								ui.add(DragValue::new(&mut rule.end.1).clamp_range(0..=59));
+								ui.label("↓");
+								ui.add(TextEdit::singleline(&mut rule.download_limit).desired_width(50.0).hint_text("0"));
+								ui.label("↑");
+								ui.add(TextEdit::singleline(&mut rule.upload_limit).desired_width(50.0).hint_text("0"));
+								if ui.button("Remove").clicked() {
+									wait_to_remove_rule = Some(index);
+								}
+							});
+							ui.separator();
+						}
+						if let Some(index) = wait_to_remove_rule {
+							self.settings.bandwidth_schedule.remove(index);
+						}
+						if ui.button("Add Rule").clicked() {
+							self.settings.bandwidth_schedule.push(BandwidthRule::default());
+						}
+					}
+					ui.collapsing("Categories", |ui| {
+						ui.checkbox(&mut self.settings.category_subfolders, "Save to a subfolder named after the category");
+						let mut wait_to_remove_category = None;
+						for (index, category) in self.settings.categories.iter_mut().enumerate() {
+							CollapsingHeader::new(category.name.clone())
+							.id_source(format!("category{}", index))
+							.show(ui, |ui| {
+								category_editor(ui, format!("category{}grid", index), category);
+								if ui.button("Remove").clicked() {
+									wait_to_remove_category = Some(index);
+								}
+							});
+						}
+						if let Some(index) = wait_to_remove_category {
+							self.settings.categories.remove(index);
+						}
+						if ui.button("Add Category").clicked() {
+							self.settings.categories.push(Category {
+								name: "Custom".to_string(),
+								..Default::default()
+							});
+						}
+					});
+					ui.collapsing("Site Rules", |ui| {
+						ui.label("The first rule matching the host applies.");
+						let mut wait_to_remove_rule = None;
+						for (index, rule) in self.settings.site_rules.iter_mut().enumerate() {
+							CollapsingHeader::new(if rule.host.is_empty() { "(Empty)".to_string() } else { rule.host.clone() })
+							.id_source(format!("site_rule{}", index))
+							.show(ui, |ui| {
+								site_rule_editor(ui, format!("site_rule{}grid", index), rule);
+								if ui.button("Remove").clicked() {
+									wait_to_remove_rule = Some(index);
+								}
+							});
+						}
+						if let Some(index) = wait_to_remove_rule {
+							self.settings.site_rules.remove(index);
+						}
+						if ui.button("Add Rule").clicked() {
+							self.settings.site_rules.push(SiteRule::default());
+						}
+					});
+					ui.collapsing("Cookie Jar", |ui| {
+						ui.label(format!("{} cookies", self.cookie_jar.len()));
+						ui.horizontal(|ui| {
+							ui.add(TextEdit::singleline(&mut self.cookie_import_path).hint_text("Path to cookies.txt"));
+							if ui.button("Import").clicked() {
+								let path = PathBuf::from(self.cookie_import_path.trim());
+								match self.cookie_jar.import(&path) {
+									Ok(count) => set_status_info(format!("Import {} cookies from `{}`", count, path.display())),
+									Err(msg) => set_status_error(format!("Failed to import cookies: {}", msg)),
+								}
+							}
+							if ui.button("Clear").clicked() {
+								match self.cookie_jar.clear() {
+									Ok(_) => set_status_info("Clear cookie jar".to_string()),
+									Err(msg) => set_status_error(format!("Failed to clear cookies: {}", msg)),
+								}
+							}
+						});
+					});
+					ui.collapsing("Credentials", |ui| {
+						ui.label("Passwords are saved in the system keyring. ~/.netrc is used as fallback.");
+						let mut wait_to_remove_credential = None;
+						Grid::new("credentials")
+						.num_columns(4)
+						.show(ui, |ui| {
+							for (index, credential) in self.settings.credentials.iter_mut().enumerate() {
+								ui.add(TextEdit::singleline(&mut credential.host).hint_text("Host, e.g. *.example.com"));
+								ui.add(TextEdit::singleline(&mut credential.user).hint_text("User"));
+								ui.add(TextEdit::singleline(&mut credential.password).password(true).hint_text("(Unchanged)"));
+								if ui.button("Remove").clicked() {
+									wait_to_remove_credential = Some(index);
+								}
+								ui.end_row();
+							}
+						});
+						if let Some(index) = wait_to_remove_credential {
+							let credential = self.settings.credentials.remove(index);
+							self.removed_credentials.push(credential);
+						}
+						if ui.button("Add Credential").clicked() {
+							self.settings.credentials.push(Credential::default());
+						}
+					});
 					if ui.button("Apply").clicked() {
 						self.apply_settings();
 					}
@@ -372,13 +1472,13 @@ impl App for DownloadManager {
 
 		// 查看聚焦请求
 		if get_focus_request() {
-			println!("Do Focus Request");
+			log::debug!("Do Focus Request");
 			frame.focus();
 			set_focus_request(false);
 		}
 
 		// 如果还有在下载的东西，那就刷新页面
-		if !all_finished {
+		if !all_finished || !self.wait_to_probe.is_empty() {
 			ctx.request_repaint();
 		} else {
 			ctx.request_repaint_after(Duration::from_secs(1));
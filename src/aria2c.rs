@@ -1,9 +1,10 @@
 use std::{thread, collections::HashMap};
 
-use aria2_ws::{Client, TaskOptions};
+use aria2_ws::{response::GlobalStat, Client, TaskOptions};
+use serde_json::{Value, json};
 use futures::executor::block_on;
 
-use crate::{data::{get_settings, set_status_info}, session::Session};
+use crate::{bandwidth::BandwidthLimit, cookies::write_task_cookies, credentials::apply_credential, data::{get_settings, set_status_error, set_status_info, set_status_warning}, proxy::apply_proxy, session::Session, settings::get_download_dir, site_rules::find_site_rule};
 
 pub const SERVER_URL: &str = "ws://127.0.0.1:6800/jsonrpc";
 
@@ -11,35 +12,68 @@ fn get_options(session: &Session) -> TaskOptions {
 	let mut opt = TaskOptions::default();
 	let settings = get_settings();
 	opt.split = Some(settings.split_num);
-	if !settings.proxy.is_empty() {
-		opt.all_proxy = Some(settings.proxy.clone());
-	}
+	apply_proxy(&settings, &session.get_url(), &mut opt);
 	opt.header = Some(vec![
-		format!("Cookie: {}", session.get_cookie()),
 		format!("User-Agent: {}", settings.user_agent),
 		format!("Referer: {}", session.get_referer()),
 	]);
-	opt.dir = Some(format!("/Users/{}/Downloads", users::get_current_username().unwrap().to_str().unwrap()));
+	opt.dir = Some(get_download_dir().to_string_lossy().to_string());
+	// 按照站点规则覆盖全局设置
+	if let Some(rule) = find_site_rule(&settings.site_rules, &session.get_url()) {
+		rule.apply(&mut opt, &session.get_referer());
+	}
+	// 从凭据或 ~/.netrc 中提供认证信息
+	apply_credential(&settings.credentials, &session.get_url(), &mut opt);
+	// 重试时从已下载的部分继续
+	if session.is_retrying() {
+		opt.r#continue = Some(true);
+	}
+	// 单个任务的设置优先
+	session.get_options().apply(&mut opt);
+	opt
+}
+
+// 探测文件信息时使用与下载相同的请求头、代理与凭据
+pub fn get_probe_options(session: &Session) -> TaskOptions {
+	let mut opt = get_options(session);
+	apply_cookie_header(session, &mut opt);
 	opt
 }
 
+fn apply_cookie_header(session: &Session, opt: &mut TaskOptions) {
+	let cookie = session.get_cookie();
+	if !cookie.is_empty() {
+		opt.header.get_or_insert_with(Vec::new).insert(0, format!("Cookie: {}", cookie));
+	}
+}
+
 pub fn add_uri(client: &Option<Client>, url: String, target_session: &mut Session) {
 	if client.is_none() {
-		set_status_info("Client is none. Please try to reconnect.".to_string());
+		set_status_warning("Client is none. Please try to reconnect.".to_string());
 		return;
 	}
+	let mut opt = get_options(target_session);
+	// 通过 cookie 文件让 aria2 在重定向到其他站点时也能带上正确的 cookie
+	let cookie_file = write_task_cookies(&target_session.get_uid(), &target_session.get_cookie_hosts(), &target_session.get_cookies());
+	match &cookie_file {
+		Some(path) => {
+			opt.extra_options.insert("load-cookies".to_string(), Value::String(path.to_string_lossy().to_string()));
+		},
+		None => apply_cookie_header(target_session, &mut opt),
+	}
+	target_session.set_cookie_file(cookie_file);
 	let gid = match block_on(
 		client.clone().unwrap()
 		.add_uri(
 			vec![url],
-			Some(get_options(&target_session)),
+			Some(opt),
 			None,
 			None
 		)
 	) {
 		Ok(gid) => gid,
 		Err(msg) => {
-			set_status_info(format!("{}", msg));
+			set_status_error(format!("{}", msg));
 			String::new()
 		}
 	};
@@ -61,6 +95,20 @@ pub fn remove(client: &Option<Client>, gid: String) {
 	});
 }
 
+pub fn remove_download_result(client: &Option<Client>, gid: String) {
+	if client.is_none() {
+		return;
+	}
+	thread::scope(|s| {
+		s.spawn(|| {
+			let _ = block_on(
+				client.clone().unwrap()
+				.remove_download_result(&gid)
+			);
+		});
+	});
+}
+
 pub fn pause(client: &Option<Client>, gid: String) {
 	if client.is_none() {
 		return;
@@ -89,6 +137,137 @@ pub fn unpause(client: &Option<Client>, gid: String) {
 	});
 }
 
+pub fn change_option(client: &Option<Client>, gid: String, session: &Session) {
+	if client.is_none() {
+		return;
+	}
+	let mut opt = get_options(session);
+	// 运行中的任务不能修改下载目录、文件名与 cookie 文件
+	opt.dir = None;
+	opt.out = None;
+	opt.extra_options.remove("load-cookies");
+	if !session.has_cookie_file() {
+		apply_cookie_header(session, &mut opt);
+	}
+	// 清空的设置需要显式地恢复为 aria2 的默认值，否则会保留之前的值
+	opt.split.get_or_insert(get_settings().split_num);
+	opt.max_connection_per_server.get_or_insert(1);
+	opt.max_download_limit.get_or_insert_with(|| "0".to_string());
+	opt.all_proxy.get_or_insert_with(String::new);
+	opt.header.get_or_insert_with(Vec::new);
+	opt.max_tries.get_or_insert(5);
+	opt.extra_options.entry("no-proxy".to_string()).or_insert(Value::String(String::new()));
+	opt.extra_options.entry("retry-wait".to_string()).or_insert(Value::String("0".to_string()));
+	thread::scope(|s| {
+		s.spawn(|| {
+			match block_on(
+				client.clone().unwrap()
+				.change_option(&gid, opt)
+			) {
+				Ok(_) => set_status_info(format!("Change options of `{}`", session.get_name())),
+				Err(msg) => set_status_error(format!("{}", msg)),
+			}
+		});
+	});
+}
+
+pub fn change_global_limit(client: &Option<Client>, limit: &BandwidthLimit) {
+	if client.is_none() {
+		return;
+	}
+	let mut opt = TaskOptions::default();
+	let download = if limit.download.is_empty() { "0".to_string() } else { limit.download.clone() };
+	let upload = if limit.upload.is_empty() { "0".to_string() } else { limit.upload.clone() };
+	opt.extra_options.insert("max-overall-download-limit".to_string(), Value::String(download));
+	opt.extra_options.insert("max-overall-upload-limit".to_string(), Value::String(upload));
+	thread::scope(|s| {
+		s.spawn(|| {
+			if let Err(msg) = block_on(
+				client.clone().unwrap()
+				.change_global_option(opt)
+			) {
+				set_status_error(format!("{}", msg));
+			}
+		});
+	});
+}
+
+pub fn change_max_concurrent_downloads(client: &Option<Client>, num: i32) {
+	if client.is_none() {
+		return;
+	}
+	let mut opt = TaskOptions::default();
+	opt.extra_options.insert("max-concurrent-downloads".to_string(), Value::String(num.to_string()));
+	thread::scope(|s| {
+		s.spawn(|| {
+			if let Err(msg) = block_on(
+				client.clone().unwrap()
+				.change_global_option(opt)
+			) {
+				set_status_error(format!("{}", msg));
+			}
+		});
+	});
+}
+
+pub enum QueueMove {
+	Top,
+	Up,
+	Down,
+	Bottom,
+}
+
+pub fn change_position(client: &Option<Client>, gid: String, queue_move: QueueMove) {
+	if client.is_none() {
+		return;
+	}
+	let (pos, how) = match queue_move {
+		QueueMove::Top => (0, "POS_SET"),
+		QueueMove::Up => (-1, "POS_CUR"),
+		QueueMove::Down => (1, "POS_CUR"),
+		QueueMove::Bottom => (0, "POS_END"),
+	};
+	thread::scope(|s| {
+		s.spawn(|| {
+			if let Err(msg) = block_on(
+				client.clone().unwrap()
+				.call_and_wait::<i32>("changePosition", vec![json!(gid), json!(pos), json!(how)])
+			) {
+				set_status_error(format!("{}", msg));
+			}
+		});
+	});
+}
+
+pub fn get_waiting(client: &Option<Client>) -> Vec<String> {
+	if client.is_none() {
+		return vec![];
+	}
+	thread::scope(|s| {
+		s.spawn(|| {
+			match block_on(
+				client.clone().unwrap()
+				.tell_waiting(0, 1000)
+			) {
+				Ok(waiting) => waiting.iter().map(|status| status.gid.clone()).collect(),
+				Err(_) => vec![],
+			}
+		}).join().unwrap()
+	})
+}
+
+pub fn get_global_stat(client: &Option<Client>) -> Option<GlobalStat> {
+	client.as_ref()?;
+	thread::scope(|s| {
+		s.spawn(|| {
+			block_on(
+				client.clone().unwrap()
+				.get_global_stat()
+			).ok()
+		}).join().unwrap()
+	})
+}
+
 pub fn get_status(client: &Option<Client>, gid: String, target_session: &mut Session) {
 	if client.is_none() {
 		return;
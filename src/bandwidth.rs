@@ -0,0 +1,163 @@
+use chrono::{Datelike, Local, Timelike};
+use serde::{Serialize, Deserialize};
+
+use crate::{data::{get_bandwidth_limit, get_settings, set_bandwidth_limit, set_bandwidth_update}, settings::Settings};
+
+pub const WEEKDAYS: [&str; 7] = [
+	"Mon",
+	"Tue",
+	"Wed",
+	"Thu",
+	"Fri",
+	"Sat",
+	"Sun",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthRule {
+	pub days: [bool; 7],
+	pub start: (u32, u32),
+	pub end: (u32, u32),
+	pub download_limit: String,
+	pub upload_limit: String,
+}
+
+impl Default for BandwidthRule {
+	fn default() -> Self {
+		Self {
+			days: [true, true, true, true, true, false, false],
+			start: (9, 0),
+			end: (18, 0),
+			download_limit: "2M".to_string(),
+			upload_limit: "".to_string(),
+		}
+	}
+}
+
+impl BandwidthRule {
+	// weekday 从周一开始计数，minute 为当天的分钟数
+	pub fn matches(&self, weekday: usize, minute: u32) -> bool {
+		let start = self.start.0 * 60 + self.start.1;
+		let end = self.end.0 * 60 + self.end.1;
+		if start <= end {
+			self.days[weekday] && minute >= start && minute < end
+		} else {
+			// 跨越午夜的规则，凌晨部分属于前一天
+			let yesterday = (weekday + 6) % 7;
+			(self.days[weekday] && minute >= start)
+			|| (self.days[yesterday] && minute < end)
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BandwidthLimit {
+	pub download: String,
+	pub upload: String,
+}
+
+impl BandwidthLimit {
+	pub fn get_download(&self) -> String {
+		format_limit(&self.download)
+	}
+
+	pub fn get_upload(&self) -> String {
+		format_limit(&self.upload)
+	}
+}
+
+fn format_limit(limit: &str) -> String {
+	let limit = limit.trim();
+	if limit.is_empty() || limit == "0" {
+		"Unlimited".to_string()
+	} else {
+		format!("{}B/s", limit)
+	}
+}
+
+pub fn get_current_limit(settings: &Settings) -> BandwidthLimit {
+	if settings.enable_bandwidth_schedule {
+		let now = Local::now();
+		let weekday = now.weekday().num_days_from_monday() as usize;
+		let minute = now.hour() * 60 + now.minute();
+		// 第一条匹配的规则生效
+		for rule in settings.bandwidth_schedule.iter() {
+			if rule.matches(weekday, minute) {
+				return BandwidthLimit {
+					download: rule.download_limit.trim().to_string(),
+					upload: rule.upload_limit.trim().to_string(),
+				};
+			}
+		}
+	}
+	BandwidthLimit {
+		download: settings.max_download_limit.trim().to_string(),
+		upload: settings.max_upload_limit.trim().to_string(),
+	}
+}
+
+pub fn listen_bandwidth_schedule() {
+	loop {
+		let limit = get_current_limit(&get_settings());
+		// 如果发生了改变，请求应用到 aria2
+		if limit != get_bandwidth_limit() {
+			set_bandwidth_limit(limit);
+			set_bandwidth_update(true);
+		}
+		// 每隔一秒刷新一次
+		std::thread::sleep(std::time::Duration::from_secs(1));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rule(days: [bool; 7], start: (u32, u32), end: (u32, u32)) -> BandwidthRule {
+		BandwidthRule {
+			days,
+			start,
+			end,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn matches_same_day_window() {
+		let rule = rule([true, false, false, false, false, false, false], (9, 0), (18, 30));
+		assert!(rule.matches(0, 9 * 60));
+		assert!(rule.matches(0, 18 * 60 + 29));
+		assert!(!rule.matches(0, 18 * 60 + 30));
+		assert!(!rule.matches(0, 8 * 60 + 59));
+		assert!(!rule.matches(1, 12 * 60));
+	}
+
+	#[test]
+	fn matches_window_crossing_midnight() {
+		// 周五 23:00 到周六 06:00
+		let rule = rule([false, false, false, false, true, false, false], (23, 0), (6, 0));
+		assert!(rule.matches(4, 23 * 60));
+		assert!(rule.matches(4, 23 * 60 + 59));
+		assert!(rule.matches(5, 0));
+		assert!(rule.matches(5, 5 * 60 + 59));
+		assert!(!rule.matches(5, 6 * 60));
+		assert!(!rule.matches(5, 23 * 60));
+		// 周五凌晨属于周四的时段
+		assert!(!rule.matches(4, 3 * 60));
+	}
+
+	#[test]
+	fn matches_sunday_night_into_monday() {
+		let rule = rule([false, false, false, false, false, false, true], (22, 0), (2, 0));
+		assert!(rule.matches(6, 22 * 60));
+		assert!(rule.matches(0, 60));
+		assert!(!rule.matches(0, 22 * 60));
+	}
+
+	#[test]
+	fn format_limit_shows_unlimited() {
+		assert_eq!(format_limit(""), "Unlimited");
+		assert_eq!(format_limit("0"), "Unlimited");
+		assert_eq!(format_limit(" 2M "), "2MB/s");
+	}
+}
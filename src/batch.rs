@@ -0,0 +1,199 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::options::SessionOptions;
+
+// 单个范围展开后的最大数量，避免误输入时生成过多任务
+const MAX_RANGE_ENTRIES: usize = 10000;
+
+static RANGE_PATTERN: OnceLock<Regex> = OnceLock::new();
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchEntry {
+	pub url: String,
+	pub options: SessionOptions,
+}
+
+// 展开 `file[001-120].jpg` 这样的范围，保留数字的位数
+pub fn expand_range(url: &str) -> Vec<String> {
+	// 展开时会递归调用，只编译一次
+	let range = RANGE_PATTERN.get_or_init(|| Regex::new(r"\[(\d+)-(\d+)\]").unwrap());
+	let caps = match range.captures(url) {
+		Some(caps) => caps,
+		None => return vec![url.to_string()],
+	};
+	let whole = caps.get(0).unwrap();
+	let width = caps[1].len();
+	let (start, end) = match (caps[1].parse::<u64>(), caps[2].parse::<u64>()) {
+		(Ok(start), Ok(end)) if start <= end => (start, end),
+		_ => return vec![url.to_string()],
+	};
+	let (prefix, suffix) = (&url[..whole.start()], &url[whole.end()..]);
+	let mut urls = vec![];
+	for i in (start..=end).take(MAX_RANGE_ENTRIES) {
+		let expanded = format!("{}{:0width$}{}", prefix, i, suffix, width = width);
+		// 后面可能还有其他范围
+		urls.extend(expand_range(&expanded));
+		if urls.len() >= MAX_RANGE_ENTRIES {
+			urls.truncate(MAX_RANGE_ENTRIES);
+			break;
+		}
+	}
+	urls
+}
+
+fn parse_i32(value: &str) -> Option<i32> {
+	value.trim().parse().ok()
+}
+
+// aria2 input file 中的选项
+fn apply_option(options: &mut SessionOptions, key: &str, value: &str) {
+	let value = value.trim();
+	match key.trim() {
+		"dir" => options.dir = value.to_string(),
+		"out" => options.out = value.to_string(),
+		"split" => options.split = parse_i32(value),
+		"max-connection-per-server" => options.max_connection_per_server = parse_i32(value),
+		"max-download-limit" => options.max_download_limit = value.to_string(),
+		"all-proxy" => options.proxy = value.to_string(),
+		"header" => options.headers.push(value.to_string()),
+		"max-tries" => options.max_tries = parse_i32(value),
+		"retry-wait" => options.retry_wait = parse_i32(value),
+		"allow-overwrite" => options.allow_overwrite = value == "true",
+		"continue" => options.resume = value == "true",
+		key => log::debug!("Ignore unsupported input file option `{}`", key),
+	}
+}
+
+// 支持每行一个地址，以及 aria2 input file 格式（地址后跟缩进的 `key=value` 选项）
+pub fn parse_batch(text: &str) -> Vec<BatchEntry> {
+	let mut entries: Vec<BatchEntry> = vec![];
+	for line in text.lines() {
+		if line.trim().is_empty() || line.trim_start().starts_with('#') {
+			continue;
+		}
+		if line.starts_with(char::is_whitespace) {
+			if let (Some(entry), Some((key, value))) = (entries.last_mut(), line.trim().split_once('=')) {
+				apply_option(&mut entry.options, key, value);
+			}
+			continue;
+		}
+		// 同一行中以 tab 分隔的是同一个文件的镜像，只使用第一个
+		let url = line.split('\t').next().unwrap_or_default().trim();
+		entries.push(BatchEntry {
+			url: url.to_string(),
+			options: SessionOptions::default(),
+		});
+	}
+	let mut results = vec![];
+	for entry in entries {
+		let urls = expand_range(&entry.url);
+		let single = urls.len() == 1;
+		for url in urls {
+			let mut options = entry.options.clone();
+			// 多个地址不能保存为同一个文件名
+			if !single {
+				options.out.clear();
+			}
+			results.push(BatchEntry { url, options });
+		}
+	}
+	results
+}
+
+// 单个条目中的选项优先于共享的选项
+pub fn merge_options(shared: &SessionOptions, entry: &SessionOptions) -> SessionOptions {
+	let mut options = shared.clone();
+	if !entry.dir.trim().is_empty() {
+		options.dir = entry.dir.clone();
+	}
+	if !entry.out.trim().is_empty() {
+		options.out = entry.out.clone();
+	}
+	if entry.split.is_some() {
+		options.split = entry.split;
+	}
+	if entry.max_connection_per_server.is_some() {
+		options.max_connection_per_server = entry.max_connection_per_server;
+	}
+	if !entry.max_download_limit.trim().is_empty() {
+		options.max_download_limit = entry.max_download_limit.clone();
+	}
+	if !entry.proxy.trim().is_empty() {
+		options.proxy = entry.proxy.clone();
+	}
+	options.headers.extend(entry.headers.clone());
+	if entry.max_tries.is_some() {
+		options.max_tries = entry.max_tries;
+	}
+	if entry.retry_wait.is_some() {
+		options.retry_wait = entry.retry_wait;
+	}
+	options.allow_overwrite |= entry.allow_overwrite;
+	options.resume |= entry.resume;
+	options
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expand_range_keeps_zero_padding() {
+		assert_eq!(
+			expand_range("http://a.com/file[008-011].jpg"),
+			vec![
+				"http://a.com/file008.jpg",
+				"http://a.com/file009.jpg",
+				"http://a.com/file010.jpg",
+				"http://a.com/file011.jpg",
+			]
+		);
+	}
+
+	#[test]
+	fn expand_range_expands_every_range() {
+		assert_eq!(
+			expand_range("http://a.com/[1-2]/[01-02].jpg"),
+			vec![
+				"http://a.com/1/01.jpg",
+				"http://a.com/1/02.jpg",
+				"http://a.com/2/01.jpg",
+				"http://a.com/2/02.jpg",
+			]
+		);
+	}
+
+	#[test]
+	fn expand_range_ignores_invalid_range() {
+		assert_eq!(expand_range("http://a.com/[5-1].jpg"), vec!["http://a.com/[5-1].jpg"]);
+		assert_eq!(expand_range("http://a.com/a.jpg"), vec!["http://a.com/a.jpg"]);
+	}
+
+	#[test]
+	fn expand_range_is_limited() {
+		assert_eq!(expand_range("http://a.com/[0-999999].jpg").len(), MAX_RANGE_ENTRIES);
+		assert_eq!(expand_range("http://a.com/[0-999]/[0-999].jpg").len(), MAX_RANGE_ENTRIES);
+	}
+
+	#[test]
+	fn parse_batch_reads_input_file_options() {
+		let text = "# comment\nhttp://a.com/a.zip\thttp://mirror.com/a.zip\n  out=b.zip\n  split=4\n  header=X-Test: 1\n\nhttp://a.com/c.zip\n";
+		let entries = parse_batch(text);
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].url, "http://a.com/a.zip");
+		assert_eq!(entries[0].options.out, "b.zip");
+		assert_eq!(entries[0].options.split, Some(4));
+		assert_eq!(entries[0].options.headers, vec!["X-Test: 1"]);
+		assert_eq!(entries[1].url, "http://a.com/c.zip");
+		assert_eq!(entries[1].options, SessionOptions::default());
+	}
+
+	#[test]
+	fn parse_batch_drops_out_for_ranges() {
+		let entries = parse_batch("http://a.com/[1-3].zip\n  out=a.zip\n  dir=sub\n");
+		assert_eq!(entries.len(), 3);
+		assert!(entries.iter().all(|e| e.options.out.is_empty() && e.options.dir == "sub"));
+	}
+}
@@ -0,0 +1,121 @@
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use url::Url;
+
+use crate::{actions::PostAction, settings::get_download_dir};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Category {
+	pub name: String,
+	// 为空时使用默认下载文件夹
+	pub dir: String,
+	pub extensions: Vec<String>,
+	pub mime_types: Vec<String>,
+	pub domains: Vec<String>,
+	pub url_regex: String,
+	pub post_actions: Vec<PostAction>,
+}
+
+fn to_list(items: &[&str]) -> Vec<String> {
+	items.iter().map(|i| i.to_string()).collect()
+}
+
+pub fn get_default_categories() -> Vec<Category> {
+	vec![
+		Category {
+			name: "Documents".to_string(),
+			extensions: to_list(&["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "txt", "md", "epub", "csv"]),
+			mime_types: to_list(&["application/pdf", "application/msword", "text/*"]),
+			..Default::default()
+		},
+		Category {
+			name: "Archives".to_string(),
+			extensions: to_list(&["zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz"]),
+			mime_types: to_list(&["application/zip", "application/x-tar", "application/gzip", "application/x-7z-compressed"]),
+			..Default::default()
+		},
+		Category {
+			name: "Video".to_string(),
+			extensions: to_list(&["mp4", "mkv", "mov", "avi", "webm", "flv", "m4v"]),
+			mime_types: to_list(&["video/*"]),
+			..Default::default()
+		},
+		Category {
+			name: "Software".to_string(),
+			extensions: to_list(&["dmg", "pkg", "app", "exe", "msi", "deb", "rpm", "appimage", "iso"]),
+			mime_types: to_list(&["application/x-apple-diskimage", "application/vnd.microsoft.portable-executable"]),
+			..Default::default()
+		},
+	]
+}
+
+impl Category {
+	fn match_extension(&self, name: &str) -> bool {
+		let name = name.to_lowercase();
+		self.extensions.iter()
+			.map(|e| e.trim().trim_start_matches('.').to_lowercase())
+			.filter(|e| !e.is_empty())
+			.any(|e| name.ends_with(&format!(".{}", e)))
+	}
+
+	fn match_mime(&self, mime: &str) -> bool {
+		let mime = mime.trim().to_lowercase();
+		if mime.is_empty() {
+			return false;
+		}
+		self.mime_types.iter()
+			.map(|m| m.trim().to_lowercase())
+			.filter(|m| !m.is_empty())
+			.any(|m| match m.strip_suffix("/*") {
+				Some(prefix) => mime.starts_with(&format!("{}/", prefix)),
+				None => mime == m,
+			})
+	}
+
+	fn match_domain(&self, url: &str) -> bool {
+		let host = match Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) {
+			Some(h) => h,
+			None => return false,
+		};
+		self.domains.iter()
+			.map(|d| d.trim().to_lowercase())
+			.filter(|d| !d.is_empty())
+			.any(|d| host == d || host.ends_with(&format!(".{}", d)))
+	}
+
+	fn match_url(&self, url: &str) -> bool {
+		if self.url_regex.trim().is_empty() {
+			return false;
+		}
+		match Regex::new(self.url_regex.trim()) {
+			Ok(r) => r.is_match(url),
+			Err(_) => false,
+		}
+	}
+
+	// 任意一条规则匹配即可
+	pub fn matches(&self, url: &str, name: &str, mime: &str) -> bool {
+		self.match_extension(name)
+		|| self.match_mime(mime)
+		|| self.match_domain(url)
+		|| self.match_url(url)
+	}
+
+	// 没有设置文件夹时，只有开启了子文件夹才使用以分类命名的文件夹
+	pub fn get_dir(&self, subfolders: bool) -> String {
+		if !self.dir.trim().is_empty() {
+			self.dir.clone()
+		} else if subfolders && !self.name.trim().is_empty() {
+			get_download_dir().join(self.name.trim()).to_string_lossy().to_string()
+		} else {
+			String::new()
+		}
+	}
+}
+
+pub fn match_category(categories: &[Category], url: &str, name: &str, mime: &str) -> Option<Category> {
+	categories.iter()
+		.find(|c| c.matches(url, name, mime))
+		.cloned()
+}
@@ -0,0 +1,307 @@
+use std::{fs::{self, OpenOptions}, io::Write, os::unix::fs::{OpenOptionsExt, PermissionsExt}, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+
+use chrono::Local;
+use url::Url;
+
+use crate::{server::Cookie, settings::get_app_support_path};
+
+const COOKIE_JAR_FILE: &str = "cookies.txt";
+const TASK_COOKIE_DIR: &str = "cookies";
+// 任务使用的 cookie 文件保留的时间
+const TASK_COOKIE_LIFETIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+fn get_cookie_jar_path() -> PathBuf {
+	get_app_support_path()
+		.join(COOKIE_JAR_FILE)
+}
+
+fn get_task_cookie_dir() -> PathBuf {
+	get_app_support_path()
+		.join(TASK_COOKIE_DIR)
+}
+
+impl Cookie {
+	pub fn is_expired(&self, now: f64) -> bool {
+		match self.expiration_date {
+			Some(expiration) if !self.session && expiration > 0.0 => expiration <= now,
+			_ => false,
+		}
+	}
+
+	pub fn matches_domain(&self, host: &str) -> bool {
+		let domain = self.domain.trim_start_matches('.').to_lowercase();
+		let host = host.to_lowercase();
+		if self.host_only {
+			host == domain
+		} else {
+			host == domain || host.ends_with(&format!(".{}", domain))
+		}
+	}
+
+	pub fn matches_path(&self, path: &str) -> bool {
+		let cookie_path = if self.path.is_empty() { "/" } else { self.path.as_str() };
+		path == cookie_path
+		|| (path.starts_with(cookie_path) && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+	}
+
+	pub fn matches_url(&self, url: &Url, now: f64) -> bool {
+		let host = match url.host_str() {
+			Some(host) => host,
+			None => return false,
+		};
+		self.matches_domain(host)
+		&& self.matches_path(url.path())
+		&& (!self.secure || url.scheme() == "https")
+		&& !self.is_expired(now)
+	}
+
+	// Netscape cookies.txt 中的一行
+	pub fn to_netscape(&self) -> String {
+		let domain = if self.host_only {
+			self.domain.trim_start_matches('.').to_string()
+		} else if self.domain.starts_with('.') {
+			self.domain.clone()
+		} else {
+			format!(".{}", self.domain)
+		};
+		let bool_str = |b: bool| if b { "TRUE" } else { "FALSE" };
+		format!(
+			"{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+			if self.http_only { "#HttpOnly_" } else { "" },
+			domain,
+			bool_str(!self.host_only),
+			if self.path.is_empty() { "/" } else { &self.path },
+			bool_str(self.secure),
+			self.expiration_date.map(|e| e as i64).unwrap_or(0),
+			self.name,
+			self.value,
+		)
+	}
+
+	pub fn from_netscape(line: &str) -> Option<Self> {
+		let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+			Some(line) => (line, true),
+			None => (line, false),
+		};
+		if line.trim().is_empty() || line.starts_with('#') {
+			return None;
+		}
+		let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+		if fields.len() < 7 {
+			return None;
+		}
+		let expiration = fields[4].trim().parse::<f64>().unwrap_or(0.0);
+		Some(Self {
+			domain: fields[0].trim().to_string(),
+			host_only: !fields[1].trim().eq_ignore_ascii_case("TRUE"),
+			http_only,
+			name: fields[5].to_string(),
+			path: fields[2].trim().to_string(),
+			same_site: String::new(),
+			secure: fields[3].trim().eq_ignore_ascii_case("TRUE"),
+			session: expiration <= 0.0,
+			store_id: String::new(),
+			value: fields[6..].join("\t"),
+			expiration_date: if expiration > 0.0 { Some(expiration) } else { None },
+		})
+	}
+
+	fn is_same(&self, other: &Self) -> bool {
+		self.name == other.name
+		&& self.path == other.path
+		&& self.domain.trim_start_matches('.').eq_ignore_ascii_case(other.domain.trim_start_matches('.'))
+	}
+}
+
+fn get_now() -> f64 {
+	Local::now().timestamp() as f64
+}
+
+pub fn parse_netscape(content: &str) -> Vec<Cookie> {
+	content.lines()
+		.filter_map(Cookie::from_netscape)
+		.collect()
+}
+
+pub fn to_netscape(cookies: &[Cookie]) -> String {
+	let mut content = "# Netscape HTTP Cookie File\n".to_string();
+	for cookie in cookies {
+		content += &cookie.to_netscape();
+		content += "\n";
+	}
+	content
+}
+
+// 只保留可以发送到目标地址的 cookie
+pub fn filter_cookies(cookies: &[Cookie], url: &str) -> Vec<Cookie> {
+	let url = match Url::parse(url) {
+		Ok(url) => url,
+		Err(_) => return vec![],
+	};
+	let now = get_now();
+	cookies.iter()
+		.filter(|c| c.matches_url(&url, now))
+		.cloned()
+		.collect()
+}
+
+// 合并 cookie，相同 domain、path 与 name 的会被替换
+pub fn merge_cookies(target: &mut Vec<Cookie>, cookies: Vec<Cookie>) {
+	for cookie in cookies {
+		target.retain(|c| !c.is_same(&cookie));
+		target.push(cookie);
+	}
+}
+
+pub struct CookieJar {
+	cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+	pub fn new() -> Self {
+		let cookies = match fs::read_to_string(get_cookie_jar_path()) {
+			Ok(content) => parse_netscape(&content),
+			Err(_) => vec![],
+		};
+		Self { cookies }
+	}
+
+	pub fn get_cookies(&self) -> Vec<Cookie> {
+		self.cookies.clone()
+	}
+
+	pub fn len(&self) -> usize {
+		self.cookies.len()
+	}
+
+	// 导入 Netscape 格式的 cookies.txt，返回导入的数量
+	pub fn import(&mut self, path: &Path) -> Result<usize, String> {
+		let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+		let now = get_now();
+		let cookies: Vec<Cookie> = parse_netscape(&content).into_iter()
+			.filter(|c| !c.is_expired(now))
+			.collect();
+		let count = cookies.len();
+		merge_cookies(&mut self.cookies, cookies);
+		self.save()?;
+		Ok(count)
+	}
+
+	pub fn clear(&mut self) -> Result<(), String> {
+		self.cookies.clear();
+		self.save()
+	}
+
+	fn save(&self) -> Result<(), String> {
+		let path = get_cookie_jar_path();
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+		}
+		write_private(&path, &to_netscape(&self.cookies)).map_err(|e| e.to_string())
+	}
+}
+
+// 只有当前用户可以读取，已存在的文件也会修改权限
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+	let mut file = OpenOptions::new()
+		.create(true)
+		.write(true)
+		.truncate(true)
+		.mode(0o600)
+		.open(path)?;
+	file.set_permissions(fs::Permissions::from_mode(0o600))?;
+	file.write_all(content.as_bytes())
+}
+
+// 为任务写入 cookie 文件，供 aria2 的 load-cookies 使用，只包含下载地址以及重定向经过的 host 的 cookie
+pub fn write_task_cookies(uid: &str, hosts: &[String], cookies: &[Cookie]) -> Option<PathBuf> {
+	let now = get_now();
+	let mut all_cookies = CookieJar::new().get_cookies();
+	merge_cookies(&mut all_cookies, cookies.to_vec());
+	all_cookies.retain(|c| !c.is_expired(now) && hosts.iter().any(|h| c.matches_domain(h)));
+	if all_cookies.is_empty() {
+		return None;
+	}
+	let dir = get_task_cookie_dir();
+	fs::create_dir_all(&dir).ok()?;
+	let path = dir.join(format!("{}.txt", uid));
+	match write_private(&path, &to_netscape(&all_cookies)) {
+		Ok(_) => Some(path),
+		Err(e) => {
+			log::warn!("Failed to write cookie file: {}", e);
+			None
+		}
+	}
+}
+
+pub fn remove_task_cookies(path: &Path) {
+	if let Err(e) = fs::remove_file(path) {
+		log::debug!("Failed to remove cookie file `{}`: {}", path.display(), e);
+	}
+}
+
+// 清理异常退出时遗留的任务 cookie 文件
+pub fn clean_task_cookies() {
+	let entries = match fs::read_dir(get_task_cookie_dir()) {
+		Ok(entries) => entries,
+		Err(_) => return,
+	};
+	for entry in entries.flatten() {
+		let expired = entry.metadata()
+			.and_then(|m| m.modified())
+			.ok()
+			.and_then(|t| SystemTime::now().duration_since(t).ok())
+			.map(|d| d > TASK_COOKIE_LIFETIME)
+			.unwrap_or(false);
+		if expired {
+			let _ = fs::remove_file(entry.path());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn from_netscape_reads_http_only_lines() {
+		let cookie = Cookie::from_netscape("#HttpOnly_.example.com\tTRUE\t/dl\tTRUE\t1893456000\tsid\tabc").unwrap();
+		assert!(cookie.http_only);
+		assert!(!cookie.host_only);
+		assert!(cookie.secure);
+		assert!(!cookie.session);
+		assert_eq!(cookie.domain, ".example.com");
+		assert_eq!(cookie.path, "/dl");
+		assert_eq!(cookie.name, "sid");
+		assert_eq!(cookie.value, "abc");
+		assert_eq!(cookie.expiration_date, Some(1893456000.0));
+		assert_eq!(cookie.to_netscape(), "#HttpOnly_.example.com\tTRUE\t/dl\tTRUE\t1893456000\tsid\tabc");
+	}
+
+	#[test]
+	fn from_netscape_skips_comments() {
+		assert!(Cookie::from_netscape("# Netscape HTTP Cookie File").is_none());
+		assert!(Cookie::from_netscape("").is_none());
+		assert!(Cookie::from_netscape("example.com\tFALSE\t/").is_none());
+	}
+
+	#[test]
+	fn from_netscape_reads_session_cookies() {
+		let cookie = Cookie::from_netscape("example.com\tFALSE\t/\tFALSE\t0\tname\ta\tb").unwrap();
+		assert!(cookie.host_only);
+		assert!(!cookie.http_only);
+		assert!(cookie.session);
+		assert_eq!(cookie.expiration_date, None);
+		assert_eq!(cookie.value, "a\tb");
+	}
+
+	#[test]
+	fn matches_url_checks_domain_path_and_scheme() {
+		let cookie = Cookie::from_netscape(".example.com\tTRUE\t/dl\tTRUE\t0\tsid\tabc").unwrap();
+		let now = get_now();
+		assert!(cookie.matches_url(&Url::parse("https://cdn.example.com/dl/a.zip").unwrap(), now));
+		assert!(!cookie.matches_url(&Url::parse("http://cdn.example.com/dl/a.zip").unwrap(), now));
+		assert!(!cookie.matches_url(&Url::parse("https://cdn.example.com/dlx").unwrap(), now));
+		assert!(!cookie.matches_url(&Url::parse("https://badexample.com/dl/").unwrap(), now));
+	}
+}
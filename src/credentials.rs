@@ -0,0 +1,344 @@
+use std::{collections::HashMap, fs::{self, OpenOptions}, io::Write, os::unix::fs::{OpenOptionsExt, PermissionsExt}, path::{Path, PathBuf}, process::{Command, Stdio}, sync::Mutex, thread};
+
+use aria2_ws::TaskOptions;
+use chacha20poly1305::{aead::{Aead, AeadCore, KeyInit, OsRng}, ChaCha20Poly1305, Key, Nonce};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use url::Url;
+use users::os::unix::UserExt;
+
+use crate::{settings::get_app_support_path, site_rules::glob_match};
+
+const KEYRING_SERVICE: &str = "com.iewnfod.ariadownloadmanager";
+
+// 账户 -> 密码，None 表示没有保存的密码
+static PASSWORD_CACHE: Mutex<Option<HashMap<String, Option<String>>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Credential {
+	// 匹配 host 的通配符
+	pub host: String,
+	pub user: String,
+	// 密码只保存在系统钥匙串或加密文件中，这里仅作为界面上的输入
+	#[serde(skip)]
+	pub password: String,
+}
+
+fn keyring_account(host: &str, user: &str) -> String {
+	format!("{}@{}", user, host)
+}
+
+fn keyring_command(action: &str, account: &str) -> Command {
+	let mut command;
+	if cfg!(target_os = "macos") {
+		command = Command::new("security");
+		match action {
+			// -w 放在最后且不带值时，security 从标准输入读取密码，避免出现在命令行中
+			"store" => command.args(["add-generic-password", "-U", "-s", KEYRING_SERVICE, "-a", account, "-w"]),
+			"lookup" => command.args(["find-generic-password", "-s", KEYRING_SERVICE, "-a", account, "-w"]),
+			_ => command.args(["delete-generic-password", "-s", KEYRING_SERVICE, "-a", account]),
+		};
+	} else {
+		command = Command::new("secret-tool");
+		if action == "store" {
+			command.args(["store", "--label", &format!("Aria Download Manager ({})", account)]);
+		} else {
+			command.arg(action);
+		}
+		command.args(["service", KEYRING_SERVICE, "account", account]);
+	}
+	command
+}
+
+fn keyring_store(account: &str, password: &str) -> Result<(), String> {
+	let output = keyring_command("store", account)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.and_then(|mut child| {
+			if let Some(mut stdin) = child.stdin.take() {
+				// security 需要输入两次密码
+				let input = if cfg!(target_os = "macos") {
+					format!("{}\n{}\n", password, password)
+				} else {
+					password.to_string()
+				};
+				stdin.write_all(input.as_bytes())?;
+			}
+			child.wait_with_output()
+		});
+	match output {
+		Ok(output) if output.status.success() => Ok(()),
+		Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+fn keyring_load(account: &str) -> Option<String> {
+	let output = keyring_command("lookup", account).output().ok()?;
+	if !output.status.success() {
+		return None;
+	}
+	let password = String::from_utf8_lossy(&output.stdout).trim_end_matches('\n').to_string();
+	if password.is_empty() { None } else { Some(password) }
+}
+
+fn get_secret_path() -> PathBuf {
+	get_app_support_path().join("credentials.bin")
+}
+
+fn get_secret_key_path() -> PathBuf {
+	get_app_support_path().join("credentials.key")
+}
+
+// 只有当前用户可以读写
+fn write_private(path: &Path, content: &[u8]) -> Result<(), String> {
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+	}
+	let mut file = OpenOptions::new()
+		.create(true)
+		.write(true)
+		.truncate(true)
+		.mode(0o600)
+		.open(path)
+		.map_err(|e| e.to_string())?;
+	// 文件已存在时 mode 不生效
+	file.set_permissions(fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+	file.write_all(content).map_err(|e| e.to_string())
+}
+
+fn get_secret_key() -> Result<Key, String> {
+	let path = get_secret_key_path();
+	if let Ok(key) = fs::read(&path) {
+		if key.len() == 32 {
+			return Ok(*Key::from_slice(&key));
+		}
+	}
+	let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+	write_private(&path, &key)?;
+	Ok(key)
+}
+
+// 钥匙串不可用时的加密文件，内容为 nonce + 加密后的 JSON
+fn read_secret_file() -> HashMap<String, String> {
+	let content = match fs::read(get_secret_path()) {
+		Ok(content) if content.len() > 12 => content,
+		_ => return HashMap::new(),
+	};
+	let (nonce, data) = content.split_at(12);
+	get_secret_key().ok()
+		.and_then(|key| ChaCha20Poly1305::new(&key).decrypt(Nonce::from_slice(nonce), data).ok())
+		.and_then(|data| serde_json::from_slice(&data).ok())
+		.unwrap_or_default()
+}
+
+fn write_secret_file(secrets: &HashMap<String, String>) -> Result<(), String> {
+	if secrets.is_empty() {
+		let _ = fs::remove_file(get_secret_path());
+		return Ok(());
+	}
+	let key = get_secret_key()?;
+	let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+	let data = serde_json::to_vec(secrets).map_err(|e| e.to_string())?;
+	let encrypted = ChaCha20Poly1305::new(&key).encrypt(&nonce, data.as_slice()).map_err(|e| e.to_string())?;
+	write_private(&get_secret_path(), &[nonce.as_slice(), &encrypted].concat())
+}
+
+fn cache_password(account: String, password: Option<String>) {
+	PASSWORD_CACHE.lock().unwrap().get_or_insert_with(HashMap::new).insert(account, password);
+}
+
+// 保存到系统钥匙串（macOS 使用 security，其他系统使用 secret-tool），失败时保存到加密文件
+pub fn store_password(host: &str, user: &str, password: &str) -> Result<(), String> {
+	let account = keyring_account(host, user);
+	if let Err(msg) = keyring_store(&account, password) {
+		log::warn!("Failed to save password to keyring, use encrypted file instead: {}", msg);
+		let mut secrets = read_secret_file();
+		secrets.insert(account.clone(), password.to_string());
+		write_secret_file(&secrets)?;
+	}
+	cache_password(account, Some(password.to_string()));
+	Ok(())
+}
+
+pub fn load_password(host: &str, user: &str) -> Option<String> {
+	let account = keyring_account(host, user);
+	keyring_load(&account).or_else(|| read_secret_file().remove(&account))
+}
+
+pub fn delete_password(host: &str, user: &str) {
+	let account = keyring_account(host, user);
+	let _ = keyring_command("delete", &account).output();
+	let mut secrets = read_secret_file();
+	if secrets.remove(&account).is_some() {
+		if let Err(msg) = write_secret_file(&secrets) {
+			log::error!("Failed to update encrypted credentials: {}", msg);
+		}
+	}
+	cache_password(account, None);
+}
+
+// 在后台读取所有密码，避免下载时在界面线程中调用钥匙串
+pub fn preload_passwords(credentials: Vec<Credential>) {
+	thread::spawn(move || {
+		for credential in credentials {
+			let (host, user) = (credential.host.trim(), credential.user.trim());
+			if host.is_empty() {
+				continue;
+			}
+			cache_password(keyring_account(host, user), load_password(host, user));
+		}
+	});
+}
+
+fn get_cached_password(host: &str, user: &str) -> Option<String> {
+	let account = keyring_account(host, user);
+	if let Some(password) = PASSWORD_CACHE.lock().unwrap().as_ref().and_then(|cache| cache.get(&account)) {
+		return password.clone();
+	}
+	// 还没有预加载时才直接读取
+	let password = load_password(host, user);
+	cache_password(account, password.clone());
+	password
+}
+
+// 从 ~/.netrc 中查找 host 对应的登录信息
+fn find_netrc(host: &str) -> Option<(String, String)> {
+	let user = users::get_user_by_uid(users::get_current_uid())?;
+	let content = fs::read_to_string(user.home_dir().join(".netrc")).ok()?;
+	parse_netrc(&content, host)
+}
+
+// 没有匹配的 machine 时使用 default
+fn parse_netrc(content: &str, host: &str) -> Option<(String, String)> {
+	// 宏定义持续到空行为止，跳过其中的内容
+	let mut in_macro = false;
+	let content: Vec<&str> = content.lines()
+		.filter(|line| {
+			if in_macro {
+				in_macro = !line.trim().is_empty();
+				return false;
+			}
+			in_macro = line.split_whitespace().next() == Some("macdef");
+			!in_macro
+		})
+		.collect();
+	let content = content.join("\n");
+	let mut tokens = content.split_whitespace();
+	let mut default = None;
+	let mut current: Option<String> = None;
+	let (mut login, mut password) = (String::new(), String::new());
+	let mut finish = |machine: &Option<String>, login: &str, password: &str| -> Option<(String, String)> {
+		match machine.as_deref() {
+			Some("") if default.is_none() => {
+				default = Some((login.to_string(), password.to_string()));
+				None
+			},
+			Some(m) if m.eq_ignore_ascii_case(host) => Some((login.to_string(), password.to_string())),
+			_ => None,
+		}
+	};
+	while let Some(token) = tokens.next() {
+		match token {
+			"machine" | "default" => {
+				if let Some(found) = finish(&current, &login, &password) {
+					return Some(found);
+				}
+				current = Some(if token == "default" { String::new() } else { tokens.next().unwrap_or_default().to_string() });
+				login.clear();
+				password.clear();
+			},
+			"login" => login = tokens.next().unwrap_or_default().to_string(),
+			"password" => password = tokens.next().unwrap_or_default().to_string(),
+			"account" => { tokens.next(); },
+			_ => {},
+		}
+	}
+	if let Some(found) = finish(&current, &login, &password) {
+		return Some(found);
+	}
+	default
+}
+
+// 界面中的凭据优先，其次为 ~/.netrc
+pub fn find_credential(credentials: &[Credential], url: &str) -> Option<(String, String)> {
+	let host = Url::parse(url).ok()?.host_str()?.to_lowercase();
+	for credential in credentials {
+		let pattern = credential.host.trim().to_lowercase();
+		if pattern.is_empty() || !glob_match(&pattern, &host) {
+			continue;
+		}
+		if let Some(password) = get_cached_password(credential.host.trim(), credential.user.trim()) {
+			return Some((credential.user.trim().to_string(), password));
+		}
+	}
+	find_netrc(&host)
+}
+
+pub fn apply_credential(credentials: &[Credential], url: &str, opt: &mut TaskOptions) {
+	// URL 中已经包含用户名时不再覆盖
+	let parsed = Url::parse(url).ok();
+	if parsed.as_ref().map(|u| !u.username().is_empty()).unwrap_or(false) {
+		return;
+	}
+	if let Some((user, password)) = find_credential(credentials, url) {
+		let prefix = if parsed.map(|u| u.scheme() == "ftp").unwrap_or(false) { "ftp" } else { "http" };
+		opt.extra_options.insert(format!("{}-user", prefix), Value::String(user));
+		opt.extra_options.insert(format!("{}-passwd", prefix), Value::String(password));
+	}
+}
+
+// 去掉 URL 中的用户名与密码，避免保存到历史记录
+pub fn strip_credentials(url: &str) -> String {
+	match Url::parse(url) {
+		Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+			let _ = parsed.set_username("");
+			let _ = parsed.set_password(None);
+			parsed.to_string()
+		},
+		_ => url.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const NETRC: &str = "machine a.example.com login alice password secret1\n\
+		machine b.example.com\n\tlogin bob\n\taccount ignored\n\tpassword secret2\n\
+		default login anonymous password guest\n";
+
+	#[test]
+	fn parse_netrc_finds_machine() {
+		assert_eq!(parse_netrc(NETRC, "a.example.com"), Some(("alice".to_string(), "secret1".to_string())));
+		assert_eq!(parse_netrc(NETRC, "B.example.com"), Some(("bob".to_string(), "secret2".to_string())));
+	}
+
+	#[test]
+	fn parse_netrc_falls_back_to_default() {
+		assert_eq!(parse_netrc(NETRC, "c.example.com"), Some(("anonymous".to_string(), "guest".to_string())));
+		assert_eq!(parse_netrc("machine a.example.com login alice password secret1", "c.example.com"), None);
+	}
+
+	#[test]
+	fn parse_netrc_prefers_machine_after_default() {
+		let content = "default login anonymous password guest machine a.example.com login alice password secret1";
+		assert_eq!(parse_netrc(content, "a.example.com"), Some(("alice".to_string(), "secret1".to_string())));
+	}
+
+	#[test]
+	fn parse_netrc_skips_macdef() {
+		let content = "macdef init\nmachine evil login x password y\n\nmachine a.example.com login alice password secret1\n";
+		assert_eq!(parse_netrc(content, "a.example.com"), Some(("alice".to_string(), "secret1".to_string())));
+		assert_eq!(parse_netrc(content, "evil"), None);
+	}
+
+	#[test]
+	fn strip_credentials_removes_userinfo() {
+		assert_eq!(strip_credentials("https://u:p@a.example.com/f"), "https://a.example.com/f");
+		assert_eq!(strip_credentials("https://a.example.com/f"), "https://a.example.com/f");
+	}
+}
@@ -1,48 +1,85 @@
-use std::process::{Command, Stdio};
+use std::{process::{Command, Stdio}, sync::{Mutex, RwLock}};
 
 use eframe::{egui::{FontData, FontDefinitions, Style, TextStyle, Visuals}, epaint::{FontFamily, FontId}};
 
-use crate::{settings::Settings, server::Info};
+use crate::{bandwidth::BandwidthLimit, export::ExportEntry, settings::Settings, server::Info, stats::GlobalStatus, status::{append_with_rotation, get_status_log_path, StatusEntry, StatusLevel}};
 
-static mut STATUS_INFO: String = String::new();
+// 状态信息也会在后台线程中写入
+static STATUS_INFO: Mutex<String> = Mutex::new(String::new());
+static STATUS_LOG: Mutex<Vec<StatusEntry>> = Mutex::new(vec![]);
 static mut WAIT_TO_START: Vec<Info> = vec![];
 static mut QUIT_REQUEST: bool = false;
 static mut FOCUS_REQUEST: bool = false;
 static mut SETTINGS_UPDATE: bool = false;
 static mut VISUAL_DARK: bool = false;
-
-static mut SETTINGS: Option<Settings> = None;
+// 以下状态会被后台线程读写，需要加锁
+static BANDWIDTH_UPDATE: Mutex<bool> = Mutex::new(false);
+static BANDWIDTH_LIMIT: Mutex<BandwidthLimit> = Mutex::new(BandwidthLimit {
+	download: String::new(),
+	upload: String::new(),
+});
+
+static SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+// 由界面线程写入，监听服务的线程读取
+static GLOBAL_STATUS: Mutex<Option<GlobalStatus>> = Mutex::new(None);
+static SESSION_ENTRIES: Mutex<Vec<(String, ExportEntry)>> = Mutex::new(vec![]);
 
 pub const CLICK_TO_COPY_PROMPT: &str = "Click to Copy";
+const MAX_STATUS_LOG: usize = 1000;
 
 pub fn set_settings(new_settings: Settings) {
-	unsafe {
-		SETTINGS = Some(new_settings);
-	}
+	*SETTINGS.write().unwrap() = Some(new_settings);
 }
 
 pub fn get_settings() -> Settings {
-	unsafe {
-		if SETTINGS.is_none() {
-			let settings = Settings::new();
-			set_settings(settings.clone());
-			settings
-		} else {
-			SETTINGS.clone().unwrap()
-		}
+	if let Some(settings) = SETTINGS.read().unwrap().as_ref() {
+		return settings.clone();
 	}
+	let settings = Settings::new();
+	set_settings(settings.clone());
+	settings
 }
 
 pub fn set_status_info(info: String) {
-	unsafe {
-		STATUS_INFO = info;
+	push_status(StatusLevel::Info, info);
+}
+
+pub fn set_status_warning(info: String) {
+	push_status(StatusLevel::Warning, info);
+}
+
+pub fn set_status_error(info: String) {
+	push_status(StatusLevel::Error, info);
+}
+
+fn push_status(level: StatusLevel, info: String) {
+	let entry = StatusEntry::new(level, info.clone());
+	match level {
+		StatusLevel::Info => log::info!("{}", info),
+		StatusLevel::Warning => log::warn!("{}", info),
+		StatusLevel::Error => log::error!("{}", info),
+	}
+	if get_settings().persist_status_log {
+		append_with_rotation(&get_status_log_path(), &entry.to_string());
+	}
+	*STATUS_INFO.lock().unwrap() = info;
+	let mut status_log = STATUS_LOG.lock().unwrap();
+	status_log.push(entry);
+	if status_log.len() > MAX_STATUS_LOG {
+		status_log.remove(0);
 	}
 }
 
+pub fn get_status_log() -> Vec<StatusEntry> {
+	STATUS_LOG.lock().unwrap().clone()
+}
+
+pub fn clear_status_log() {
+	STATUS_LOG.lock().unwrap().clear();
+}
+
 pub fn get_status_info() -> String {
-	unsafe {
-		STATUS_INFO.clone()
-	}
+	STATUS_INFO.lock().unwrap().clone()
 }
 
 pub fn add_wait_to_start(data: Info) {
@@ -111,6 +148,38 @@ pub fn set_visual_dark(v: bool) {
 	}
 }
 
+pub fn get_bandwidth_update() -> bool {
+	*BANDWIDTH_UPDATE.lock().unwrap()
+}
+
+pub fn set_bandwidth_update(b: bool) {
+	*BANDWIDTH_UPDATE.lock().unwrap() = b;
+}
+
+pub fn get_bandwidth_limit() -> BandwidthLimit {
+	BANDWIDTH_LIMIT.lock().unwrap().clone()
+}
+
+pub fn set_bandwidth_limit(limit: BandwidthLimit) {
+	*BANDWIDTH_LIMIT.lock().unwrap() = limit;
+}
+
+pub fn get_global_status() -> Option<GlobalStatus> {
+	GLOBAL_STATUS.lock().unwrap().clone()
+}
+
+pub fn set_global_status(status: GlobalStatus) {
+	*GLOBAL_STATUS.lock().unwrap() = Some(status);
+}
+
+pub fn get_session_entries() -> Vec<(String, ExportEntry)> {
+	SESSION_ENTRIES.lock().unwrap().clone()
+}
+
+pub fn set_session_entries(entries: Vec<(String, ExportEntry)>) {
+	*SESSION_ENTRIES.lock().unwrap() = entries;
+}
+
 pub fn get_global_fonts() -> FontDefinitions {
 	let mut fonts = FontDefinitions::default();
 	let font_name = "LXGW".to_string();
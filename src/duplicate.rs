@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicatePolicy {
+	#[default]
+	Ask,
+	Skip,
+	Resume,
+	Overwrite,
+	Rename,
+}
+
+impl DuplicatePolicy {
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Self::Ask => "Ask",
+			Self::Skip => "Skip",
+			Self::Resume => "Resume Existing",
+			Self::Overwrite => "Overwrite",
+			Self::Rename => "Rename",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+	// 重复的进行中任务
+	pub uid: Option<String>,
+	pub reason: String,
+}
+
+// 在文件名后加上序号，直到与已有的文件都不冲突
+pub fn unique_name(dir: &Path, name: &str, taken: &[PathBuf]) -> String {
+	let path = Path::new(name);
+	let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or(name.to_string());
+	let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+	let mut index = 1;
+	loop {
+		let candidate = format!("{} ({}){}", stem, index, extension);
+		let candidate_path = dir.join(&candidate);
+		if !candidate_path.exists() && !taken.contains(&candidate_path) {
+			return candidate;
+		}
+		index += 1;
+	}
+}
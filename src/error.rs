@@ -0,0 +1,198 @@
+use std::fmt::Display;
+
+// aria2 的退出码
+// https://aria2.github.io/manual/en/html/aria2c.html#exit-status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadError {
+	Unknown,
+	Timeout,
+	ResourceNotFound,
+	TooManyNotFound,
+	TooSlow,
+	NetworkProblem,
+	Unfinished,
+	ResumeNotSupported,
+	DiskFull,
+	PieceLengthMismatch,
+	AlreadyDownloading,
+	SameInfoHash,
+	FileExists,
+	RenameFailed,
+	OpenFileFailed,
+	CreateFileFailed,
+	FileIoError,
+	CreateDirectoryFailed,
+	NameResolutionFailed,
+	MetalinkParseFailed,
+	FtpCommandFailed,
+	BadHttpResponse,
+	TooManyRedirects,
+	AuthFailed,
+	BencodeParseFailed,
+	TorrentCorrupted,
+	BadMagnet,
+	BadOption,
+	ServerOverloaded,
+	RpcParseFailed,
+	ChecksumFailed,
+	Other(String),
+}
+
+impl DownloadError {
+	pub fn from_code(code: &str) -> Option<Self> {
+		let error = match code.trim() {
+			"" | "0" => return None,
+			"1" => Self::Unknown,
+			"2" => Self::Timeout,
+			"3" => Self::ResourceNotFound,
+			"4" => Self::TooManyNotFound,
+			"5" => Self::TooSlow,
+			"6" => Self::NetworkProblem,
+			"7" => Self::Unfinished,
+			"8" => Self::ResumeNotSupported,
+			"9" => Self::DiskFull,
+			"10" => Self::PieceLengthMismatch,
+			"11" => Self::AlreadyDownloading,
+			"12" => Self::SameInfoHash,
+			"13" => Self::FileExists,
+			"14" => Self::RenameFailed,
+			"15" => Self::OpenFileFailed,
+			"16" => Self::CreateFileFailed,
+			"17" => Self::FileIoError,
+			"18" => Self::CreateDirectoryFailed,
+			"19" => Self::NameResolutionFailed,
+			"20" => Self::MetalinkParseFailed,
+			"21" => Self::FtpCommandFailed,
+			"22" => Self::BadHttpResponse,
+			"23" => Self::TooManyRedirects,
+			"24" => Self::AuthFailed,
+			"25" => Self::BencodeParseFailed,
+			"26" => Self::TorrentCorrupted,
+			"27" => Self::BadMagnet,
+			"28" => Self::BadOption,
+			"29" => Self::ServerOverloaded,
+			"30" => Self::RpcParseFailed,
+			"32" => Self::ChecksumFailed,
+			other => Self::Other(other.to_string()),
+		};
+		Some(error)
+	}
+
+	pub fn get_code(&self) -> String {
+		let code = match self {
+			Self::Unknown => "1",
+			Self::Timeout => "2",
+			Self::ResourceNotFound => "3",
+			Self::TooManyNotFound => "4",
+			Self::TooSlow => "5",
+			Self::NetworkProblem => "6",
+			Self::Unfinished => "7",
+			Self::ResumeNotSupported => "8",
+			Self::DiskFull => "9",
+			Self::PieceLengthMismatch => "10",
+			Self::AlreadyDownloading => "11",
+			Self::SameInfoHash => "12",
+			Self::FileExists => "13",
+			Self::RenameFailed => "14",
+			Self::OpenFileFailed => "15",
+			Self::CreateFileFailed => "16",
+			Self::FileIoError => "17",
+			Self::CreateDirectoryFailed => "18",
+			Self::NameResolutionFailed => "19",
+			Self::MetalinkParseFailed => "20",
+			Self::FtpCommandFailed => "21",
+			Self::BadHttpResponse => "22",
+			Self::TooManyRedirects => "23",
+			Self::AuthFailed => "24",
+			Self::BencodeParseFailed => "25",
+			Self::TorrentCorrupted => "26",
+			Self::BadMagnet => "27",
+			Self::BadOption => "28",
+			Self::ServerOverloaded => "29",
+			Self::RpcParseFailed => "30",
+			Self::ChecksumFailed => "32",
+			Self::Other(code) => code,
+		};
+		code.to_string()
+	}
+
+	pub fn get_description(&self) -> String {
+		let description = match self {
+			Self::Unknown => "Unknown error",
+			Self::Timeout => "Connection timed out",
+			Self::ResourceNotFound => "Resource not found",
+			Self::TooManyNotFound => "Resource not found too many times",
+			Self::TooSlow => "Download speed was too slow",
+			Self::NetworkProblem => "Network problem",
+			Self::Unfinished => "Download was not finished",
+			Self::ResumeNotSupported => "Server does not support resuming",
+			Self::DiskFull => "Not enough disk space",
+			Self::PieceLengthMismatch => "Piece length differs from control file",
+			Self::AlreadyDownloading => "Same file is already being downloaded",
+			Self::SameInfoHash => "Same torrent is already being downloaded",
+			Self::FileExists => "File already exists",
+			Self::RenameFailed => "Failed to rename file",
+			Self::OpenFileFailed => "Failed to open existing file",
+			Self::CreateFileFailed => "Failed to create file",
+			Self::FileIoError => "File I/O error",
+			Self::CreateDirectoryFailed => "Failed to create directory",
+			Self::NameResolutionFailed => "Domain name resolution failed",
+			Self::MetalinkParseFailed => "Failed to parse Metalink",
+			Self::FtpCommandFailed => "FTP command failed",
+			Self::BadHttpResponse => "Bad or unexpected HTTP response",
+			Self::TooManyRedirects => "Too many redirects",
+			Self::AuthFailed => "Authentication failed",
+			Self::BencodeParseFailed => "Failed to parse torrent data",
+			Self::TorrentCorrupted => "Torrent file is corrupted",
+			Self::BadMagnet => "Magnet link is invalid",
+			Self::BadOption => "Invalid download option",
+			Self::ServerOverloaded => "Server is overloaded or under maintenance",
+			Self::RpcParseFailed => "Failed to parse RPC request",
+			Self::ChecksumFailed => "Checksum validation failed",
+			Self::Other(_) => "Unrecognized error",
+		};
+		description.to_string()
+	}
+
+	pub fn get_suggestion(&self) -> String {
+		let suggestion = match self {
+			Self::Timeout | Self::NetworkProblem | Self::TooSlow =>
+				"Check your network connection or proxy settings and retry.",
+			Self::ResourceNotFound | Self::TooManyNotFound =>
+				"The link may have expired. Get a fresh link from the webpage.",
+			Self::ResumeNotSupported =>
+				"Remove the partial file and download again from the beginning.",
+			Self::DiskFull =>
+				"Free up disk space or choose another download folder.",
+			Self::AlreadyDownloading | Self::SameInfoHash =>
+				"Wait for the existing download to finish.",
+			Self::FileExists | Self::RenameFailed =>
+				"Rename or remove the existing file and retry.",
+			Self::OpenFileFailed | Self::CreateFileFailed | Self::FileIoError | Self::CreateDirectoryFailed =>
+				"Check the permissions of the download folder.",
+			Self::NameResolutionFailed =>
+				"Check the url and your DNS settings.",
+			Self::BadHttpResponse | Self::ServerOverloaded | Self::FtpCommandFailed =>
+				"The server had a problem. Retry later.",
+			Self::TooManyRedirects =>
+				"The link may require logging in. Download it from the webpage again.",
+			Self::AuthFailed =>
+				"Check the username and password for this site.",
+			Self::PieceLengthMismatch | Self::ChecksumFailed =>
+				"The file is damaged. Remove it and download again.",
+			Self::BadOption =>
+				"Check the options of this download.",
+			Self::MetalinkParseFailed | Self::BencodeParseFailed | Self::TorrentCorrupted | Self::BadMagnet =>
+				"The link or torrent is invalid.",
+			Self::Unknown | Self::Unfinished | Self::RpcParseFailed | Self::Other(_) =>
+				"Retry the download. If it keeps failing, check the error message.",
+		};
+		suggestion.to_string()
+	}
+}
+
+impl Display for DownloadError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} ({})", self.get_description(), self.get_code())
+	}
+}
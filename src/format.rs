@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+const SIZE_UNITS: [&str; 6] = [
+	"B",
+	"KB",
+	"MB",
+	"GB",
+	"TB",
+	"PB",
+];
+
+pub fn format_size(size: u64) -> String {
+	let mut result = size as f64;
+	let mut unit_index = 0;
+	while result >= 1024.0 && unit_index < SIZE_UNITS.len() - 1 {
+		result /= 1024.0;
+		unit_index += 1;
+	}
+	if unit_index == 0 {
+		format!("{}{}", size, SIZE_UNITS[0])
+	} else {
+		format!("{:.2}{}", result, SIZE_UNITS[unit_index])
+	}
+}
+
+pub fn format_speed(speed: u64) -> String {
+	format_size(speed) + "/s"
+}
+
+pub fn format_duration(duration: Duration) -> String {
+	let seconds = duration.as_secs();
+	let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+	if hours >= 24 {
+		format!("{}d {:02}:{:02}:{:02}", hours / 24, hours % 24, minutes, seconds)
+	} else {
+		format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+	}
+}
+
+pub fn format_percent(part: u64, total: u64) -> String {
+	if total == 0 {
+		"0.00%".to_string()
+	} else {
+		format!("{:.2}%", part as f64 / total as f64 * 100.0)
+	}
+}
@@ -4,7 +4,7 @@ use aria2_ws::Client;
 use chrono::{Local, Datelike, Timelike};
 use serde::{Serialize, Deserialize};
 
-use crate::{settings::get_app_support_path, session::Session};
+use crate::{actions::ActionResult, credentials::strip_credentials, settings::get_app_support_path, session::Session, options::SessionOptions, error::DownloadError};
 
 const HISTORY_FILE: &str = "history.json";
 
@@ -20,20 +20,33 @@ pub struct HistorySession {
 	file: String,
 	name: String,
 	time: (i32, u32, u32, u32, u32, u32),
+	#[serde(default)]
+	options: SessionOptions,
+	#[serde(default)]
+	error_code: String,
+	#[serde(default)]
+	action_results: Vec<ActionResult>,
+	#[serde(default)]
+	category: String,
 }
 
 impl HistorySession {
-	pub fn new(url: String, webpage: String, file: String, name: String) -> Self {
+	pub fn new(session: &Session) -> Self {
 		let time = Local::now();
 		Self {
-			url,
-			webpage,
-			file,
-			name,
+			url: strip_credentials(&session.get_url()),
+			webpage: session.get_webpage(),
+			file: session.get_file(),
+			name: session.get_name(),
 			time: (
 				time.year(), time.month(), time.day(),
 				time.hour(), time.minute(), time.second(),
 			),
+			// 和 url 一样不保存凭据
+			options: session.get_options().without_secrets(),
+			error_code: session.get_error().map(|e| e.get_code()).unwrap_or_default(),
+			action_results: session.get_action_results(),
+			category: session.get_category(),
 		}
 	}
 
@@ -51,6 +64,8 @@ impl HistorySession {
 
 	pub fn resume(&self, sessions: &mut HashMap<String, Session>, client: Option<Client>) {
 		let mut session = Session::new(self.url.clone(), client).unwrap();
+		session.set_options(self.options.clone());
+		session.set_category(self.category.clone());
 		session.start();
 		sessions.insert(session.get_uid(), session);
 	}
@@ -59,6 +74,22 @@ impl HistorySession {
 		format!("{}-{}-{} {}:{}:{}", self.time.0, self.time.1, self.time.2, self.time.3, self.time.4, self.time.5)
 	}
 
+	pub fn get_error(&self) -> Option<DownloadError> {
+		DownloadError::from_code(&self.error_code)
+	}
+
+	pub fn get_options(&self) -> SessionOptions {
+		self.options.clone()
+	}
+
+	pub fn get_category(&self) -> String {
+		self.category.clone()
+	}
+
+	pub fn get_action_results(&self) -> Vec<ActionResult> {
+		self.action_results.clone()
+	}
+
 	pub fn get_webpage(&self) -> String {
 		self.webpage.clone()
 	}
@@ -75,6 +106,10 @@ impl PartialEq for HistorySession {
 		self.url == other.url
 		&& self.file == other.file
 		&& self.name == other.name
+		&& self.options == other.options
+		&& self.error_code == other.error_code
+		&& self.action_results == other.action_results
+		&& self.category == other.category
 	}
 }
 
@@ -137,12 +172,7 @@ impl History {
 	}
 
 	pub fn add_session(&mut self, session: Session) {
-		let history_session = HistorySession::new(
-			session.get_url(),
-			session.get_webpage(),
-			session.get_file(),
-			session.get_name(),
-		);
+		let history_session = HistorySession::new(&session);
 		// 如果和之前的相同，那就不需要重新写一遍文件
 		if self.sessions.contains_key(&session.get_uid()) {
 			if self.sessions[&session.get_uid()] == history_session {
@@ -162,6 +192,14 @@ impl History {
 		data
 	}
 
+	// 任务重新提交后 uid 会改变，保留原有的记录
+	pub fn rekey(&mut self, uid: &str, new_uid: &str) {
+		if let Some(session) = self.sessions.remove(uid) {
+			self.sessions.insert(new_uid.to_string(), session);
+			self.save();
+		}
+	}
+
 	pub fn remove(&mut self, uid: &String) {
 		if self.sessions.contains_key(uid) {
 			self.sessions.remove(uid);
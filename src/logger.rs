@@ -0,0 +1,136 @@
+use std::{env, path::PathBuf, sync::{OnceLock, RwLock}};
+
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use regex::Regex;
+
+use crate::{settings::get_app_support_path, status::append_with_rotation};
+
+const LOG_FILE: &str = "adm.log";
+// 环境变量优先于设置
+const LOG_ENV: &str = "ADM_LOG";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+fn get_log_path() -> PathBuf {
+	get_app_support_path()
+		.join(LOG_FILE)
+}
+
+// 形如 `info,aria_download_manager::server=debug`
+#[derive(Debug, Clone)]
+struct LogFilter {
+	default_level: LevelFilter,
+	modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+	fn parse(spec: &str) -> Self {
+		let mut filter = Self {
+			default_level: LevelFilter::Info,
+			modules: vec![],
+		};
+		for part in spec.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+			match part.split_once('=') {
+				Some((module, level)) => {
+					if let Ok(level) = level.trim().parse() {
+						filter.modules.push((module.trim().to_string(), level));
+					}
+				},
+				None => {
+					if let Ok(level) = part.parse() {
+						filter.default_level = level;
+					}
+				}
+			}
+		}
+		// 最长的模块名优先匹配
+		filter.modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+		filter
+	}
+
+	fn get_level(&self, target: &str) -> LevelFilter {
+		for (module, level) in self.modules.iter() {
+			if target == module || target.starts_with(&format!("{}::", module)) {
+				return *level;
+			}
+		}
+		self.default_level
+	}
+
+	fn get_max_level(&self) -> LevelFilter {
+		self.modules.iter()
+			.map(|(_, level)| *level)
+			.fold(self.default_level, |a, b| a.max(b))
+	}
+}
+
+struct Logger {
+	filter: RwLock<LogFilter>,
+	redactions: Vec<(Regex, &'static str)>,
+}
+
+impl Logger {
+	fn new(spec: &str) -> Self {
+		Self {
+			filter: RwLock::new(LogFilter::parse(spec)),
+			redactions: vec![
+				// 请求头中的 Cookie 与认证信息
+				(Regex::new(r"(?i)\b((?:set-)?cookie|(?:proxy-)?authorization)(\s*[:=]\s*)[^\n\]]*").unwrap(), "$1$2<redacted>"),
+				// aria2 的密码选项
+				(Regex::new(r"(?i)\b((?:http|ftp)-passwd|password)(\s*[:=]\s*)\S+").unwrap(), "$1$2<redacted>"),
+				// Url 中的用户名和密码
+				(Regex::new(r"://[^/\s:@]+:[^/\s@]+@").unwrap(), "://<redacted>@"),
+			],
+		}
+	}
+
+	fn redact(&self, message: String) -> String {
+		let mut message = message;
+		for (regex, replacement) in self.redactions.iter() {
+			message = regex.replace_all(&message, *replacement).to_string();
+		}
+		message
+	}
+}
+
+impl Log for Logger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.filter.read().unwrap().get_level(metadata.target())
+	}
+
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		let line = format!("{} {:<5} [{}] {}",
+			Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+			record.level(),
+			record.target(),
+			self.redact(record.args().to_string()),
+		);
+		eprintln!("{}", line);
+		append_with_rotation(&get_log_path(), &line);
+	}
+
+	fn flush(&self) {}
+}
+
+fn get_filter_spec(settings_spec: &str) -> String {
+	env::var(LOG_ENV).unwrap_or(settings_spec.to_string())
+}
+
+pub fn init(settings_spec: &str) {
+	let logger = LOGGER.get_or_init(|| Logger::new(&get_filter_spec(settings_spec)));
+	if log::set_logger(logger).is_ok() {
+		log::set_max_level(logger.filter.read().unwrap().get_max_level());
+	}
+}
+
+pub fn set_filter(settings_spec: &str) {
+	if let Some(logger) = LOGGER.get() {
+		let filter = LogFilter::parse(&get_filter_spec(settings_spec));
+		log::set_max_level(filter.get_max_level());
+		*logger.filter.write().unwrap() = filter;
+	}
+}
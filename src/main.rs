@@ -1,6 +1,7 @@
 use std::thread;
 
-use data::listen_theme_change;
+use bandwidth::listen_bandwidth_schedule;
+use data::{get_settings, listen_theme_change};
 use eframe::{NativeOptions, epaint::vec2, run_native, IconData};
 use image::DynamicImage;
 
@@ -12,13 +13,44 @@ mod settings;
 mod aria2c;
 mod history;
 mod widgets;
+mod options;
+mod bandwidth;
+mod scheduler;
+mod retry;
+mod error;
+mod format;
+mod stats;
+mod status;
+mod logger;
+mod notification;
+mod actions;
+mod category;
+mod site_rules;
+mod proxy;
+mod pac;
+mod credentials;
+mod cookies;
+mod probe;
+mod duplicate;
+mod batch;
+mod export;
 
 #[tokio::main]
 async fn main() {
+    // 初始化日志
+    logger::init(&get_settings().log_filter);
+    // 预先读取保存的密码
+    credentials::preload_passwords(get_settings().credentials.clone());
+    // 清理过期的任务 cookie 文件
+    cookies::clean_task_cookies();
     // 启用监听服务
     tokio::spawn(server::listen());
     // 监听系统主题变化
     thread::spawn(|| listen_theme_change());
+    // 按照计划调整全局限速
+    thread::spawn(listen_bandwidth_schedule);
+    // 在后台加载与执行 PAC 脚本
+    thread::spawn(proxy::listen_pac);
     // 应用设置
     // 图标
     let icon_source: Option<DynamicImage> = match image::open("assets/icon.iconset/icon_512x512.png") {
@@ -0,0 +1,88 @@
+use std::{io::{BufRead, BufReader}, process::{Command, Stdio}, thread};
+
+use serde::{Serialize, Deserialize};
+
+const APP_NAME: &str = "Aria Download Manager";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationMode {
+	#[default]
+	All,
+	FailuresOnly,
+	Off,
+}
+
+impl NotificationMode {
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Self::All => "All",
+			Self::FailuresOnly => "Failures Only",
+			Self::Off => "Off",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyState {
+	Running,
+	Completed,
+	Failed,
+}
+
+fn open_path(path: &str) {
+	let program = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+	if let Ok(mut child) = Command::new(program).arg(path).spawn() {
+		let _ = child.wait();
+	}
+}
+
+// 通过 D-Bus 的 org.freedesktop.Notifications 发送通知，点击按钮后打开文件或文件夹
+fn notify_freedesktop(title: &str, body: &str, file: &str, dir: &str) {
+	let mut command = Command::new("notify-send");
+	command.arg("--app-name").arg(APP_NAME);
+	if !file.is_empty() {
+		command.arg("--action=open=Open");
+	}
+	if !dir.is_empty() {
+		command.arg("--action=folder=Open Folder");
+	}
+	command.arg(title).arg(body);
+	let mut child = match command.stdout(Stdio::piped()).spawn() {
+		Ok(c) => c,
+		Err(e) => {
+			log::warn!("Failed to send notification: {}", e);
+			return;
+		}
+	};
+	// 用户点击按钮后，notify-send 会输出对应的 action
+	if let Some(stdout) = child.stdout.take() {
+		if let Some(Ok(action)) = BufReader::new(stdout).lines().next() {
+			match action.trim() {
+				"open" => open_path(file),
+				"folder" => open_path(dir),
+				_ => {}
+			}
+		}
+	}
+	let _ = child.wait();
+}
+
+fn notify_macos(title: &str, body: &str) {
+	let script = format!(
+		"display notification {:?} with title {:?}",
+		body, title
+	);
+	if let Ok(mut child) = Command::new("osascript").arg("-e").arg(script).spawn() {
+		let _ = child.wait();
+	}
+}
+
+pub fn notify(title: String, body: String, file: String, dir: String) {
+	thread::spawn(move || {
+		if cfg!(target_os = "macos") {
+			notify_macos(&title, &body);
+		} else {
+			notify_freedesktop(&title, &body, &file, &dir);
+		}
+	});
+}
@@ -0,0 +1,115 @@
+use std::path::{Component, Path};
+
+use aria2_ws::TaskOptions;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{actions::PostAction, credentials::strip_credentials, probe::sanitize_filename, settings::get_download_dir};
+
+// 包含凭据的请求头，不能导出或者保存
+const SECRET_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionOptions {
+	pub split: Option<i32>,
+	pub max_connection_per_server: Option<i32>,
+	pub max_download_limit: String,
+	pub proxy: String,
+	pub headers: Vec<String>,
+	pub max_tries: Option<i32>,
+	pub retry_wait: Option<i32>,
+	pub post_actions: Vec<PostAction>,
+	pub dir: String,
+	pub out: String,
+	pub allow_overwrite: bool,
+	pub resume: bool,
+}
+
+impl SessionOptions {
+	pub fn get_headers(&self) -> Vec<String> {
+		self.headers.iter()
+			.map(|h| h.trim().to_string())
+			.filter(|h| !h.is_empty())
+			.collect()
+	}
+
+	// 去掉请求头与代理地址中的凭据
+	pub fn without_secrets(&self) -> Self {
+		let mut options = self.clone();
+		options.headers.retain(|h| {
+			let name = h.split(':').next().unwrap_or_default().trim().to_lowercase();
+			!SECRET_HEADERS.contains(&name.as_str())
+		});
+		if !options.proxy.trim().is_empty() {
+			options.proxy = strip_credentials(options.proxy.trim());
+		}
+		options
+	}
+
+	// 本地 API 没有认证，不能由调用者指定完成后操作，也不能写到下载文件夹以外或覆盖文件
+	pub fn restrict_for_api(&mut self) {
+		if !self.post_actions.is_empty() {
+			log::warn!("Drop post actions from api request");
+			self.post_actions.clear();
+		}
+		self.allow_overwrite = false;
+		if !self.out.trim().is_empty() {
+			self.out = sanitize_filename(&self.out);
+		}
+		if !self.dir.trim().is_empty() {
+			let base = get_download_dir();
+			let path = Path::new(self.dir.trim());
+			let path = if path.is_absolute() { path.to_path_buf() } else { base.join(path) };
+			if path.components().any(|c| c == Component::ParentDir) || !path.starts_with(&base) {
+				log::warn!("Drop download folder `{}` outside of `{}` from api request", self.dir, base.display());
+				self.dir = String::new();
+			} else {
+				self.dir = path.to_string_lossy().to_string();
+			}
+		}
+	}
+
+	// 将单个任务的设置覆盖到全局设置上
+	pub fn apply(&self, opt: &mut TaskOptions) {
+		if !self.dir.trim().is_empty() {
+			opt.dir = Some(self.dir.trim().to_string());
+		}
+		if !self.out.trim().is_empty() {
+			opt.out = Some(sanitize_filename(&self.out));
+		}
+		// 覆盖或继续已有文件时不自动重命名
+		if self.allow_overwrite {
+			opt.extra_options.insert("allow-overwrite".to_string(), Value::String("true".to_string()));
+			opt.auto_file_renaming = Some(false);
+		}
+		if self.resume {
+			opt.r#continue = Some(true);
+			opt.auto_file_renaming = Some(false);
+		}
+		if let Some(split) = self.split {
+			opt.split = Some(split);
+		}
+		if let Some(max_connection_per_server) = self.max_connection_per_server {
+			opt.max_connection_per_server = Some(max_connection_per_server);
+		}
+		if !self.max_download_limit.trim().is_empty() {
+			opt.max_download_limit = Some(self.max_download_limit.trim().to_string());
+		}
+		if !self.proxy.trim().is_empty() {
+			opt.all_proxy = Some(self.proxy.trim().to_string());
+		}
+		let headers = self.get_headers();
+		if !headers.is_empty() {
+			let mut all_headers = opt.header.clone().unwrap_or_default();
+			all_headers.extend(headers);
+			opt.header = Some(all_headers);
+		}
+		if let Some(max_tries) = self.max_tries {
+			opt.max_tries = Some(max_tries);
+		}
+		if let Some(retry_wait) = self.retry_wait {
+			opt.extra_options.insert("retry-wait".to_string(), Value::String(retry_wait.to_string()));
+		}
+	}
+}
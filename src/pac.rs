@@ -0,0 +1,301 @@
+use std::{collections::HashMap, net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket}, sync::Mutex};
+
+use boa_engine::{js_string, native_function::NativeFunctionPointer, Context, JsArgs, JsResult, JsString, JsValue, NativeFunction, Source};
+
+use crate::site_rules::glob_match;
+
+// 避免脚本中的死循环卡住后台线程
+const MAX_LOOP_ITERATIONS: u64 = 100_000;
+const MAX_RECURSION: usize = 256;
+
+// host -> 解析结果，重新加载脚本时清空
+static DNS_CACHE: Mutex<Option<HashMap<String, Option<IpAddr>>>> = Mutex::new(None);
+
+// 标准 PAC 辅助函数中不需要访问网络的部分
+const PAC_UTILS: &str = r#"
+function isPlainHostName(host) {
+	return host.indexOf('.') == -1;
+}
+function dnsDomainIs(host, domain) {
+	host = host.toLowerCase();
+	domain = domain.toLowerCase();
+	return host.length >= domain.length && host.substring(host.length - domain.length) == domain;
+}
+function localHostOrDomainIs(host, hostdom) {
+	host = host.toLowerCase();
+	hostdom = hostdom.toLowerCase();
+	return host == hostdom || (host.indexOf('.') == -1 && hostdom.indexOf(host + '.') == 0);
+}
+function dnsDomainLevels(host) {
+	return host.split('.').length - 1;
+}
+function alert(message) {}
+
+var __days = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+var __months = ['JAN', 'FEB', 'MAR', 'APR', 'MAY', 'JUN', 'JUL', 'AUG', 'SEP', 'OCT', 'NOV', 'DEC'];
+
+function __args(args) {
+	var list = Array.prototype.slice.call(args);
+	var gmt = list.length > 0 && list[list.length - 1] == 'GMT';
+	if (gmt) {
+		list.pop();
+	}
+	return { list: list, gmt: gmt, now: new Date() };
+}
+function __inRange(value, start, end) {
+	return start <= end ? value >= start && value <= end : value >= start || value <= end;
+}
+function weekdayRange() {
+	var a = __args(arguments);
+	var day = a.gmt ? a.now.getUTCDay() : a.now.getDay();
+	var start = __days.indexOf(String(a.list[0]).toUpperCase());
+	var end = a.list.length > 1 ? __days.indexOf(String(a.list[1]).toUpperCase()) : start;
+	return start >= 0 && end >= 0 && __inRange(day, start, end);
+}
+function timeRange() {
+	var a = __args(arguments);
+	var n = a.list.map(function (v) { return parseInt(v, 10); });
+	var h = a.gmt ? a.now.getUTCHours() : a.now.getHours();
+	var m = a.gmt ? a.now.getUTCMinutes() : a.now.getMinutes();
+	var s = a.gmt ? a.now.getUTCSeconds() : a.now.getSeconds();
+	var current = h * 3600 + m * 60 + s;
+	switch (n.length) {
+		case 1: return h == n[0];
+		case 2: return __inRange(current, n[0] * 3600, n[1] * 3600 - 1);
+		case 4: return __inRange(current, n[0] * 3600 + n[1] * 60, n[2] * 3600 + n[3] * 60 + 59);
+		case 6: return __inRange(current, n[0] * 3600 + n[1] * 60 + n[2], n[3] * 3600 + n[4] * 60 + n[5]);
+		default: return false;
+	}
+}
+function __dateParts(list) {
+	var parts = {};
+	for (var i = 0; i < list.length; i++) {
+		var month = __months.indexOf(String(list[i]).toUpperCase());
+		var value = parseInt(list[i], 10);
+		if (month >= 0) {
+			parts.month = month;
+		} else if (value > 31) {
+			parts.year = value;
+		} else {
+			parts.day = value;
+		}
+	}
+	return parts;
+}
+// 只比较参数中出现的部分
+function __dateValue(parts, fields) {
+	return (fields.year !== undefined ? parts.year : 0) * 10000
+		+ (fields.month !== undefined ? parts.month : 0) * 100
+		+ (fields.day !== undefined ? parts.day : 0);
+}
+function dateRange() {
+	var a = __args(arguments);
+	var count = a.list.length;
+	if (count == 0 || (count > 1 && count % 2 == 1)) {
+		return false;
+	}
+	var half = count == 1 ? 1 : count / 2;
+	var start = __dateParts(a.list.slice(0, half));
+	var end = count == 1 ? start : __dateParts(a.list.slice(half));
+	var now = {
+		year: a.gmt ? a.now.getUTCFullYear() : a.now.getFullYear(),
+		month: a.gmt ? a.now.getUTCMonth() : a.now.getMonth(),
+		day: a.gmt ? a.now.getUTCDate() : a.now.getDate(),
+	};
+	return __inRange(__dateValue(now, start), __dateValue(start, start), __dateValue(end, start));
+}
+"#;
+
+fn resolve(host: &str) -> Option<IpAddr> {
+	if let Ok(ip) = host.parse::<IpAddr>() {
+		return Some(ip);
+	}
+	if let Some(ip) = DNS_CACHE.lock().unwrap().as_ref().and_then(|cache| cache.get(host)) {
+		return *ip;
+	}
+	let ip = (host, 0).to_socket_addrs().ok().and_then(|addrs| {
+		let addrs: Vec<IpAddr> = addrs.map(|a| a.ip()).collect();
+		addrs.iter().find(|ip| ip.is_ipv4()).or(addrs.first()).copied()
+	});
+	DNS_CACHE.lock().unwrap().get_or_insert_with(HashMap::new).insert(host.to_string(), ip);
+	ip
+}
+
+// 通过 UDP 连接获取本机地址，不会真正发送数据
+fn my_ip_address() -> String {
+	UdpSocket::bind("0.0.0.0:0")
+		.and_then(|socket| socket.connect("8.8.8.8:80").map(|_| socket))
+		.and_then(|socket| socket.local_addr())
+		.map(|addr| addr.ip().to_string())
+		.unwrap_or("127.0.0.1".to_string())
+}
+
+fn is_in_net(host: &str, pattern: &str, mask: &str) -> bool {
+	let ip = match resolve(host) {
+		Some(IpAddr::V4(ip)) => u32::from(ip),
+		_ => return false,
+	};
+	match (pattern.parse::<Ipv4Addr>(), mask.parse::<Ipv4Addr>()) {
+		(Ok(pattern), Ok(mask)) => ip & u32::from(mask) == u32::from(pattern) & u32::from(mask),
+		_ => false,
+	}
+}
+
+fn get_arg(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<String> {
+	Ok(args.get_or_undefined(index).to_string(context)?.to_std_string_escaped())
+}
+
+fn js_dns_resolve(_: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+	let host = get_arg(args, 0, context)?;
+	Ok(resolve(&host).map(|ip| JsValue::from(JsString::from(ip.to_string()))).unwrap_or(JsValue::null()))
+}
+
+fn js_is_resolvable(_: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+	let host = get_arg(args, 0, context)?;
+	Ok(JsValue::from(resolve(&host).is_some()))
+}
+
+fn js_is_in_net(_: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+	let (host, pattern, mask) = (get_arg(args, 0, context)?, get_arg(args, 1, context)?, get_arg(args, 2, context)?);
+	Ok(JsValue::from(is_in_net(&host, &pattern, &mask)))
+}
+
+fn js_my_ip_address(_: &JsValue, _: &[JsValue], _: &mut Context) -> JsResult<JsValue> {
+	Ok(JsValue::from(JsString::from(my_ip_address())))
+}
+
+fn js_sh_exp_match(_: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+	let (text, pattern) = (get_arg(args, 0, context)?, get_arg(args, 1, context)?);
+	Ok(JsValue::from(glob_match(&pattern, &text)))
+}
+
+// 使用 JS 引擎执行的 PAC 脚本，只能在创建它的线程中使用
+pub struct PacScript {
+	context: Context,
+}
+
+impl PacScript {
+	pub fn parse(script: &str) -> Result<Self, String> {
+		*DNS_CACHE.lock().unwrap() = None;
+		let mut context = Context::default();
+		context.runtime_limits_mut().set_loop_iteration_limit(MAX_LOOP_ITERATIONS);
+		context.runtime_limits_mut().set_recursion_limit(MAX_RECURSION);
+		let natives: [(JsString, usize, NativeFunctionPointer); 5] = [
+			(js_string!("dnsResolve"), 1, js_dns_resolve),
+			(js_string!("isResolvable"), 1, js_is_resolvable),
+			(js_string!("isInNet"), 3, js_is_in_net),
+			(js_string!("myIpAddress"), 0, js_my_ip_address),
+			(js_string!("shExpMatch"), 2, js_sh_exp_match),
+		];
+		for (name, length, function) in natives {
+			context.register_global_callable(name, length, NativeFunction::from_fn_ptr(function))
+				.map_err(|e| e.to_string())?;
+		}
+		context.eval(Source::from_bytes(PAC_UTILS)).map_err(|e| e.to_string())?;
+		context.eval(Source::from_bytes(script)).map_err(|e| e.to_string())?;
+		let function = context.global_object()
+			.get(js_string!("FindProxyForURL"), &mut context)
+			.map_err(|e| e.to_string())?;
+		if !function.is_callable() {
+			return Err("Missing FindProxyForURL".to_string());
+		}
+		Ok(Self { context })
+	}
+
+	pub fn find_proxy(&mut self, url: &str, host: &str) -> Result<String, String> {
+		let context = &mut self.context;
+		let function = context.global_object()
+			.get(js_string!("FindProxyForURL"), context)
+			.map_err(|e| e.to_string())?;
+		let function = function.as_callable().ok_or("Missing FindProxyForURL".to_string())?;
+		let args = [JsValue::from(JsString::from(url)), JsValue::from(JsString::from(host))];
+		let result = function.call(&JsValue::undefined(), &args, context).map_err(|e| e.to_string())?;
+		if result.is_null_or_undefined() {
+			return Err("FindProxyForURL returned nothing".to_string());
+		}
+		result.to_string(context)
+			.map(|s| s.to_std_string_escaped())
+			.map_err(|e| e.to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CORPORATE: &str = r#"
+		// 内网直连
+		function FindProxyForURL(url, host) {
+			if (isPlainHostName(host) || isInNet(host, "10.0.0.0", "255.0.0.0")) {
+				return "DIRECT";
+			} else if (shExpMatch(host, "*.example.com") && !dnsDomainIs(host, "public.example.com")) {
+				return "DIRECT";
+			}
+			var proxy = "PROXY proxy.corp:8080";
+			return proxy + "; DIRECT";
+		}
+	"#;
+
+	#[test]
+	fn corporate_pac() {
+		let mut pac = PacScript::parse(CORPORATE).unwrap();
+		assert_eq!(pac.find_proxy("http://intranet/", "intranet").unwrap(), "DIRECT");
+		assert_eq!(pac.find_proxy("http://10.1.2.3/a", "10.1.2.3").unwrap(), "DIRECT");
+		assert_eq!(pac.find_proxy("http://a.example.com/", "a.example.com").unwrap(), "DIRECT");
+		assert_eq!(
+			pac.find_proxy("http://public.example.com/", "public.example.com").unwrap(),
+			"PROXY proxy.corp:8080; DIRECT"
+		);
+		assert_eq!(pac.find_proxy("http://11.1.2.3/", "11.1.2.3").unwrap(), "PROXY proxy.corp:8080; DIRECT");
+	}
+
+	#[test]
+	fn ordinary_javascript_is_supported() {
+		let script = r#"
+			var proxies = { internal: "DIRECT", other: "PROXY p:8080" }, count = 0;
+			function kind(host) {
+				for (var i = 0; i < 3; i++) {
+					count++;
+				}
+				switch (host.toLowerCase().split(".").pop()) {
+					case "local": return "internal";
+					default: return "other";
+				}
+			}
+			function FindProxyForURL(url, host) {
+				if (url.substring(0, 6) == "ftp://") return "DIRECT";
+				if (weekdayRange("SUN", "SAT") && timeRange(0, 24) && dnsDomainLevels(host) >= 0) {
+					return proxies[kind(host)];
+				}
+				return "PROXY never:1";
+			}
+		"#;
+		let mut pac = PacScript::parse(script).unwrap();
+		assert_eq!(pac.find_proxy("http://nas.LOCAL/", "nas.LOCAL").unwrap(), "DIRECT");
+		assert_eq!(pac.find_proxy("https://a.com/", "a.com").unwrap(), "PROXY p:8080");
+		assert_eq!(pac.find_proxy("ftp://a.com/", "a.com").unwrap(), "DIRECT");
+	}
+
+	#[test]
+	fn date_range_matches_current_year() {
+		let script = r#"function FindProxyForURL(url, host) {
+			var year = new Date().getFullYear();
+			if (dateRange(year) && dateRange(1, 31) && dateRange("JAN", "DEC") && !dateRange(year + 1)) return "DIRECT";
+			return "PROXY p:1";
+		}"#;
+		let mut pac = PacScript::parse(script).unwrap();
+		assert_eq!(pac.find_proxy("http://a.com/", "a.com").unwrap(), "DIRECT");
+	}
+
+	#[test]
+	fn invalid_script_is_rejected() {
+		assert!(PacScript::parse("function FindProxyForURL(url, host) {").is_err());
+		assert!(PacScript::parse("var a = 1;").is_err());
+	}
+
+	#[test]
+	fn endless_loop_is_stopped() {
+		let mut pac = PacScript::parse("function FindProxyForURL(url, host) { while (true) {} }").unwrap();
+		assert!(pac.find_proxy("http://a.com/", "a.com").is_err());
+	}
+}
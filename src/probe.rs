@@ -0,0 +1,309 @@
+use std::{io::Write, process::{Command, Stdio}, sync::{Arc, Mutex}, thread};
+
+use aria2_ws::TaskOptions;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+const DEFAULT_NAME: &str = "download";
+const PROBE_TIMEOUT_SECONDS: u64 = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeResult {
+	// 来自 Content-Disposition 的文件名
+	pub disposition_name: Option<String>,
+	// 根据重定向后的地址推测的文件名，只用于显示
+	pub url_name: Option<String>,
+	pub size: Option<u64>,
+	pub mime: String,
+	// 重定向经过的 host，任务的 cookie 文件只包含这些 host 的 cookie
+	pub redirect_hosts: Vec<String>,
+}
+
+// 在后台线程中探测，外层的 None 表示尚未完成
+#[derive(Debug, Clone)]
+pub struct ProbeHandle(Arc<Mutex<Option<Option<ProbeResult>>>>);
+
+impl ProbeHandle {
+	// 任务选项也在后台线程中生成，PAC 与钥匙串不会阻塞界面
+	pub fn spawn<F>(url: String, get_options: F) -> Self
+	where
+		F: FnOnce() -> TaskOptions + Send + 'static,
+	{
+		let result = Arc::new(Mutex::new(None));
+		let thread_result = result.clone();
+		thread::spawn(move || {
+			let probed = probe(&url, &get_options());
+			*thread_result.lock().unwrap() = Some(probed);
+		});
+		Self(result)
+	}
+
+	pub fn try_take(&self) -> Option<Option<ProbeResult>> {
+		self.0.lock().unwrap().take()
+	}
+}
+
+pub fn can_probe(url: &str) -> bool {
+	Url::parse(url).map(|u| matches!(u.scheme(), "http" | "https")).unwrap_or(false)
+}
+
+// 去掉文件名中不允许的字符
+pub fn sanitize_filename(name: &str) -> String {
+	let name: String = name.chars()
+		.map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+		.collect();
+	let mut name = name.trim().trim_matches('.').trim().to_string();
+	while name.len() > 255 {
+		name.pop();
+	}
+	if name.is_empty() {
+		DEFAULT_NAME.to_string()
+	} else {
+		name
+	}
+}
+
+fn decode(s: &str) -> String {
+	percent_decode_str(s).decode_utf8_lossy().to_string()
+}
+
+// 根据 URL 推测文件名，磁力链接使用 dn 参数
+pub fn fallback_name(url: &Url) -> String {
+	if url.scheme() == "magnet" {
+		let mut hash = String::new();
+		for (key, value) in url.query_pairs() {
+			match key.as_ref() {
+				"dn" if !value.trim().is_empty() => return sanitize_filename(&value),
+				"xt" => hash = value.rsplit(':').next().unwrap_or_default().to_string(),
+				_ => {},
+			}
+		}
+		return sanitize_filename(&format!("magnet-{}", hash.chars().take(8).collect::<String>()));
+	}
+	let segment = url.path_segments()
+		.and_then(|mut s| s.rfind(|s| !s.is_empty()).map(decode))
+		.unwrap_or_default();
+	if !segment.is_empty() {
+		sanitize_filename(&segment)
+	} else {
+		sanitize_filename(url.host_str().unwrap_or(DEFAULT_NAME))
+	}
+}
+
+// 解析 Content-Disposition 中的文件名，filename* 优先
+pub fn parse_content_disposition(value: &str) -> Option<String> {
+	let mut name = None;
+	for part in value.split(';') {
+		let (key, v) = match part.split_once('=') {
+			Some((key, v)) => (key.trim().to_lowercase(), v.trim()),
+			None => continue,
+		};
+		match key.as_str() {
+			"filename*" => {
+				// 格式为 charset'lang'encoded
+				let encoded = v.trim_matches('"').splitn(3, '\'').last().unwrap_or_default();
+				let decoded = decode(encoded);
+				if !decoded.is_empty() {
+					return Some(sanitize_filename(&decoded));
+				}
+			},
+			"filename" => {
+				let decoded = decode(v.trim_matches('"'));
+				if !decoded.is_empty() {
+					name = Some(sanitize_filename(&decoded));
+				}
+			},
+			_ => {},
+		}
+	}
+	name
+}
+
+// 解析最后一次响应的头部，以及最终地址
+fn parse_headers(output: &str) -> ProbeResult {
+	let mut result = ProbeResult::default();
+	let (headers, effective_url) = output.rsplit_once('\n').unwrap_or((output, ""));
+	// 相对地址不会改变 host，无法解析时忽略即可
+	for line in headers.lines() {
+		let location = match line.split_once(':') {
+			Some((key, value)) if key.trim().eq_ignore_ascii_case("location") => value.trim(),
+			_ => continue,
+		};
+		if let Some(host) = Url::parse(location).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) {
+			if !result.redirect_hosts.contains(&host) {
+				result.redirect_hosts.push(host);
+			}
+		}
+	}
+	// 只保留重定向后最后一个响应
+	let last = headers.rsplit("\nHTTP/").next().unwrap_or(headers);
+	// 服务器不支持 HEAD 时不使用错误页面的信息
+	let success = last.lines().next()
+		.and_then(|l| l.split_whitespace().nth(1))
+		.map(|code| code.starts_with('2'))
+		.unwrap_or(false);
+	for line in last.lines().skip(1) {
+		if !success {
+			break;
+		}
+		let (key, value) = match line.split_once(':') {
+			Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+			None => continue,
+		};
+		match key.as_str() {
+			"content-disposition" => result.disposition_name = parse_content_disposition(value),
+			"content-length" => result.size = value.parse().ok(),
+			"content-type" => result.mime = value.split(';').next().unwrap_or_default().trim().to_lowercase(),
+			_ => {},
+		}
+	}
+	result.url_name = Url::parse(effective_url.trim()).ok()
+		.map(|u| fallback_name(&u))
+		.filter(|n| n != DEFAULT_NAME);
+	result
+}
+
+fn quote_config(value: &str) -> String {
+	let value: String = value.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+	format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// curl 配置文件，通过标准输入传递，避免请求头与密码出现在命令行中
+fn curl_config(url: &str, opt: &TaskOptions) -> String {
+	let mut lines = vec![format!("url = {}", quote_config(url))];
+	for header in opt.header.iter().flatten() {
+		lines.push(format!("header = {}", quote_config(header)));
+	}
+	// 与 aria2 使用相同的代理
+	if let Some(proxy) = &opt.all_proxy {
+		lines.push(format!("proxy = {}", quote_config(proxy)));
+	}
+	let extra = |key: &str| opt.extra_options.get(key).and_then(|v| v.as_str()).map(|v| v.to_string());
+	if let Some(no_proxy) = extra("no-proxy") {
+		lines.push(format!("noproxy = {}", quote_config(&no_proxy)));
+	}
+	if let (Some(user), Some(password)) = (extra("http-user"), extra("http-passwd")) {
+		lines.push(format!("user = {}", quote_config(&format!("{}:{}", user, password))));
+	}
+	lines.join("\n") + "\n"
+}
+
+// 发送 HEAD 请求获取文件名、大小以及 MIME 类型，使用与下载时相同的请求头、代理与凭据
+pub fn probe(url: &str, opt: &TaskOptions) -> Option<ProbeResult> {
+	if !can_probe(url) {
+		return None;
+	}
+	let output = Command::new("curl")
+		.arg("-sIL")
+		.arg("--max-time").arg(PROBE_TIMEOUT_SECONDS.to_string())
+		.arg("-w").arg("\n%{url_effective}")
+		.arg("-K").arg("-")
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.and_then(|mut child| {
+			if let Some(mut stdin) = child.stdin.take() {
+				stdin.write_all(curl_config(url, opt).as_bytes())?;
+			}
+			child.wait_with_output()
+		})
+		.ok()?;
+	if !output.status.success() {
+		log::debug!("Failed to probe `{}`", url);
+		return None;
+	}
+	let output = String::from_utf8_lossy(&output.stdout).replace('\r', "");
+	Some(parse_headers(&output))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn content_disposition_prefers_extended_filename() {
+		assert_eq!(
+			parse_content_disposition("attachment; filename=\"fallback.zip\"; filename*=UTF-8''%E6%96%87%E4%BB%B6.zip"),
+			Some("文件.zip".to_string())
+		);
+		assert_eq!(
+			parse_content_disposition("attachment; filename*=utf-8'en'a%20b.txt; filename=\"c.txt\""),
+			Some("a b.txt".to_string())
+		);
+	}
+
+	#[test]
+	fn content_disposition_sanitizes_filename() {
+		assert_eq!(parse_content_disposition("attachment; filename=\"../../etc/passwd\""), Some("_.._etc_passwd".to_string()));
+		assert_eq!(parse_content_disposition("attachment; FILENAME=report.pdf"), Some("report.pdf".to_string()));
+		assert_eq!(parse_content_disposition("inline"), None);
+	}
+
+	#[test]
+	fn parse_headers_uses_last_response_of_redirect_chain() {
+		let output = "HTTP/1.1 302 Found\n\
+			Location: https://cdn.example.com/files/a.zip?token=1\n\
+			Content-Type: text/html\n\
+			\n\
+			HTTP/1.1 301 Moved Permanently\n\
+			Location: /files/b.zip\n\
+			\n\
+			HTTP/2 200\n\
+			content-type: application/zip; charset=binary\n\
+			content-length: 1024\n\
+			content-disposition: attachment; filename=\"real.zip\"\n\
+			\n\
+			https://cdn.example.com/files/b.zip";
+		let result = parse_headers(output);
+		assert_eq!(result.disposition_name, Some("real.zip".to_string()));
+		assert_eq!(result.url_name, Some("b.zip".to_string()));
+		assert_eq!(result.size, Some(1024));
+		assert_eq!(result.mime, "application/zip");
+		assert_eq!(result.redirect_hosts, vec!["cdn.example.com"]);
+	}
+
+	#[test]
+	fn parse_headers_ignores_error_responses() {
+		let output = "HTTP/1.1 405 Method Not Allowed\n\
+			Content-Type: text/html\n\
+			Content-Length: 100\n\
+			\n\
+			https://example.com/download?id=1";
+		let result = parse_headers(output);
+		assert_eq!(result.disposition_name, None);
+		assert_eq!(result.size, None);
+		assert_eq!(result.mime, "");
+		// 与默认名称相同时不作为推测的文件名
+		assert_eq!(result.url_name, None);
+	}
+
+	#[test]
+	fn curl_config_quotes_values_and_uses_credentials() {
+		let mut opt = TaskOptions {
+			header: Some(vec!["Cookie: a=\"1\"".to_string(), "X-Bad: a\r\nInjected: 1".to_string()]),
+			all_proxy: Some("http://proxy:8080".to_string()),
+			..Default::default()
+		};
+		opt.extra_options.insert("no-proxy".to_string(), "localhost".into());
+		opt.extra_options.insert("http-user".to_string(), "alice".into());
+		opt.extra_options.insert("http-passwd".to_string(), "p\\w".into());
+		assert_eq!(
+			curl_config("https://a.com/f", &opt),
+			"url = \"https://a.com/f\"\n\
+			header = \"Cookie: a=\\\"1\\\"\"\n\
+			header = \"X-Bad: aInjected: 1\"\n\
+			proxy = \"http://proxy:8080\"\n\
+			noproxy = \"localhost\"\n\
+			user = \"alice:p\\\\w\"\n"
+		);
+	}
+
+	#[test]
+	fn fallback_name_uses_magnet_display_name() {
+		let url = Url::parse("magnet:?xt=urn:btih:0123456789abcdef&dn=Some%20File").unwrap();
+		assert_eq!(fallback_name(&url), "Some File");
+		let url = Url::parse("magnet:?xt=urn:btih:0123456789abcdef").unwrap();
+		assert_eq!(fallback_name(&url), "magnet-01234567");
+	}
+}
@@ -0,0 +1,230 @@
+use std::{env, fs, process::Command, sync::{mpsc::{channel, RecvTimeoutError, Sender}, Mutex}, thread, time::{Duration, Instant}};
+
+use aria2_ws::TaskOptions;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::{data::{get_settings, set_status_warning}, pac::PacScript, settings::Settings};
+
+const PAC_CACHE_DURATION: Duration = Duration::from_secs(10 * 60);
+// 界面线程等待 PAC 结果的最长时间
+const PAC_EVAL_TIMEOUT: Duration = Duration::from_secs(1);
+
+enum PacMessage {
+	Reload,
+	Loaded(String, Result<String, String>),
+	Find(String, String, Sender<Result<String, String>>),
+}
+
+static PAC_SENDER: Mutex<Option<Sender<PacMessage>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxyMode {
+	None,
+	#[default]
+	Manual,
+	Environment,
+	Pac,
+}
+
+impl ProxyMode {
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Self::None => "None",
+			Self::Manual => "Manual",
+			Self::Environment => "Environment",
+			Self::Pac => "PAC",
+		}
+	}
+}
+
+fn split_no_proxy(list: &str) -> Vec<String> {
+	list.split(|c: char| c == ',' || c.is_whitespace())
+		.map(|s| s.trim().trim_start_matches('.').to_lowercase())
+		.filter(|s| !s.is_empty())
+		.collect()
+}
+
+// no_proxy 中的条目匹配 host 本身或其子域名
+pub fn matches_no_proxy(list: &str, host: &str) -> bool {
+	let host = host.to_lowercase();
+	split_no_proxy(list).iter().any(|entry| {
+		entry == "*"
+		|| host == *entry
+		|| host.ends_with(&format!(".{}", entry))
+	})
+}
+
+fn get_env_no_proxy() -> String {
+	env::var("no_proxy")
+		.or_else(|_| env::var("NO_PROXY"))
+		.unwrap_or_default()
+}
+
+fn load_pac(location: &str) -> Result<String, String> {
+	let location = location.trim();
+	if location.starts_with("http://") || location.starts_with("https://") {
+		let output = Command::new("curl")
+			.arg("-sfL")
+			.arg("--max-time").arg("10")
+			.arg(location)
+			.output()
+			.map_err(|e| e.to_string())?;
+		if !output.status.success() {
+			return Err(format!("curl exited with {}", output.status));
+		}
+		Ok(String::from_utf8_lossy(&output.stdout).to_string())
+	} else {
+		let path = location.strip_prefix("file://").unwrap_or(location);
+		fs::read_to_string(path).map_err(|e| e.to_string())
+	}
+}
+
+// 在后台线程中定期下载并执行 PAC 脚本，下载时继续使用旧的脚本
+pub fn listen_pac() {
+	let (sender, receiver) = channel();
+	*PAC_SENDER.lock().unwrap() = Some(sender.clone());
+	// 当前脚本以及对应的地址
+	let mut script: Option<(String, PacScript)> = None;
+	let mut loaded: Option<(String, Instant)> = None;
+	let mut loading = false;
+	loop {
+		let settings = get_settings();
+		let location = settings.pac_url.trim().to_string();
+		let expired = match &loaded {
+			Some((loaded_location, time)) => *loaded_location != location || time.elapsed() > PAC_CACHE_DURATION,
+			None => true,
+		};
+		let mut reload = settings.proxy_mode == ProxyMode::Pac && expired && !location.is_empty();
+		match receiver.recv_timeout(Duration::from_secs(1)) {
+			Ok(PacMessage::Reload) => reload = settings.proxy_mode == ProxyMode::Pac && !location.is_empty(),
+			Ok(PacMessage::Loaded(location, content)) => {
+				loading = false;
+				loaded = Some((location.clone(), Instant::now()));
+				match content.and_then(|content| PacScript::parse(&content)) {
+					Ok(new_script) => script = Some((location, new_script)),
+					Err(msg) => {
+						set_status_warning(format!("Failed to load PAC file `{}`: {}", location, msg));
+						// 地址改变后不再使用旧的脚本
+						if script.as_ref().map(|(l, _)| *l != location).unwrap_or(false) {
+							script = None;
+						}
+					},
+				}
+			},
+			Ok(PacMessage::Find(url, host, reply)) => {
+				let result = match script.as_mut() {
+					Some((_, script)) => script.find_proxy(&url, &host),
+					None => Err("PAC file is not loaded".to_string()),
+				};
+				let _ = reply.send(result);
+			},
+			Err(RecvTimeoutError::Timeout) => {},
+			Err(RecvTimeoutError::Disconnected) => break,
+		}
+		if reload && !loading {
+			loading = true;
+			let sender = sender.clone();
+			thread::spawn(move || {
+				let content = load_pac(&location);
+				let _ = sender.send(PacMessage::Loaded(location, content));
+			});
+		}
+	}
+}
+
+// 设置改变后重新加载 PAC 脚本
+pub fn reload_pac() {
+	if let Some(sender) = PAC_SENDER.lock().unwrap().as_ref() {
+		let _ = sender.send(PacMessage::Reload);
+	}
+}
+
+// 只等待很短的时间，不会在界面线程中下载脚本
+fn find_pac_proxy(url: &str, host: &str) -> Result<String, String> {
+	let sender = PAC_SENDER.lock().unwrap().clone().ok_or("PAC is not running".to_string())?;
+	let (reply, result) = channel();
+	sender.send(PacMessage::Find(url.to_string(), host.to_string(), reply))
+		.map_err(|_| "PAC is not running".to_string())?;
+	result.recv_timeout(PAC_EVAL_TIMEOUT)
+		.map_err(|_| "PAC evaluation timed out".to_string())?
+}
+
+// 将 PAC 的结果转换为 aria2 可以使用的代理地址，使用第一个支持的项
+fn pac_result_to_proxy(result: &str) -> Result<Option<String>, String> {
+	for item in result.split(';') {
+		let mut parts = item.split_whitespace();
+		let kind = match parts.next() {
+			Some(kind) => kind.to_uppercase(),
+			None => continue,
+		};
+		let address = parts.next().unwrap_or_default();
+		match kind.as_str() {
+			"PROXY" | "HTTP" => return Ok(Some(format!("http://{}", address))),
+			"HTTPS" => return Ok(Some(format!("https://{}", address))),
+			"DIRECT" => return Ok(None),
+			// aria2 不支持 SOCKS 代理
+			_ => log::debug!("Skip unsupported PAC proxy `{}`", item.trim()),
+		}
+	}
+	Err(format!("No supported proxy in PAC result `{}`", result))
+}
+
+// 根据代理模式为任务设置 all-proxy 与 no-proxy
+pub fn apply_proxy(settings: &Settings, url: &str, opt: &mut TaskOptions) {
+	let host = Url::parse(url).ok()
+		.and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+		.unwrap_or_default();
+	let (proxy, no_proxy) = match settings.proxy_mode {
+		ProxyMode::None => (None, String::new()),
+		ProxyMode::Manual => {
+			let proxy = settings.proxy.trim();
+			(if proxy.is_empty() { None } else { Some(proxy.to_string()) }, settings.no_proxy.clone())
+		},
+		ProxyMode::Environment => (env_proxy::for_url_str(url).to_string(), get_env_no_proxy()),
+		ProxyMode::Pac => {
+			if matches_no_proxy(&settings.no_proxy, &host) {
+				(None, settings.no_proxy.clone())
+			} else {
+				// PAC 不可用时直接连接，不让任务失败
+				let proxy = match find_pac_proxy(url, &host).and_then(|result| pac_result_to_proxy(&result)) {
+					Ok(proxy) => proxy,
+					Err(msg) => {
+						set_status_warning(format!("{}, connect to `{}` directly", msg, host));
+						None
+					},
+				};
+				(proxy, settings.no_proxy.clone())
+			}
+		},
+	};
+	if let Some(proxy) = proxy {
+		opt.all_proxy = Some(proxy);
+	}
+	let no_proxy = split_no_proxy(&no_proxy);
+	if !no_proxy.is_empty() {
+		opt.extra_options.insert("no-proxy".to_string(), Value::String(no_proxy.join(",")));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pac_result_uses_first_supported_proxy() {
+		assert_eq!(pac_result_to_proxy("PROXY p:8080; DIRECT").unwrap(), Some("http://p:8080".to_string()));
+		assert_eq!(pac_result_to_proxy("SOCKS5 s:1080; HTTPS p:443").unwrap(), Some("https://p:443".to_string()));
+		assert_eq!(pac_result_to_proxy("DIRECT").unwrap(), None);
+		assert!(pac_result_to_proxy("SOCKS s:1080").is_err());
+	}
+
+	#[test]
+	fn no_proxy_matches_subdomains() {
+		assert!(matches_no_proxy("localhost, .example.com", "a.example.com"));
+		assert!(matches_no_proxy("example.com", "example.com"));
+		assert!(!matches_no_proxy("example.com", "badexample.com"));
+		assert!(matches_no_proxy("*", "anything"));
+	}
+}
@@ -0,0 +1,78 @@
+use std::{fmt::Display, time::Duration};
+
+use chrono::Local;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+	pub enabled: bool,
+	pub max_attempts: usize,
+	pub initial_delay_seconds: u64,
+	pub max_delay_seconds: u64,
+	pub backoff_factor: f32,
+	pub retryable_codes: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			max_attempts: 5,
+			initial_delay_seconds: 5,
+			max_delay_seconds: 300,
+			backoff_factor: 2.0,
+			// 超时、速度过慢、网络问题、域名解析失败、响应头错误、服务器暂时无法处理
+			retryable_codes: vec!["2", "5", "6", "19", "22", "29"]
+				.into_iter().map(|c| c.to_string()).collect(),
+		}
+	}
+}
+
+impl RetryPolicy {
+	pub fn should_retry(&self, error_code: &str, attempts: usize) -> bool {
+		self.enabled
+		&& attempts < self.max_attempts
+		&& self.retryable_codes.iter().any(|c| c.trim() == error_code)
+	}
+
+	// 指数退避，不超过最大等待时间
+	pub fn get_delay(&self, attempts: usize) -> Duration {
+		let delay = self.initial_delay_seconds as f32 * self.backoff_factor.max(1.0).powi(attempts as i32);
+		Duration::from_secs_f32(delay.min(self.max_delay_seconds as f32))
+	}
+
+	pub fn get_retryable_codes(&self) -> String {
+		self.retryable_codes.join(",")
+	}
+
+	pub fn set_retryable_codes(&mut self, codes: &str) {
+		// 保留原始输入，匹配时再去除空白
+		self.retryable_codes = codes.split(',')
+			.map(|c| c.to_string())
+			.collect();
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+	time: String,
+	error: String,
+	error_message: String,
+}
+
+impl RetryAttempt {
+	pub fn new(error: String, error_message: String) -> Self {
+		Self {
+			time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+			error,
+			error_message,
+		}
+	}
+}
+
+impl Display for RetryAttempt {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} [{}] {}", self.time, self.error, self.error_message)
+	}
+}
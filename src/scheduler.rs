@@ -0,0 +1,168 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use aria2_ws::Client;
+use chrono::{Local, TimeZone, Duration, Timelike};
+use serde::{Serialize, Deserialize};
+
+use crate::{data::set_status_error, format::format_duration, options::SessionOptions, server::Cookie, session::Session, settings::get_app_support_path};
+
+const SCHEDULED_FILE: &str = "scheduled.json";
+
+fn get_scheduled_path() -> PathBuf {
+	get_app_support_path()
+		.join(SCHEDULED_FILE)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StartCondition {
+	#[default]
+	Now,
+	// 本地时间戳（秒）
+	At(i64),
+	WhenIdle,
+	// 等待磁盘空间足够
+	WhenSpace,
+}
+
+impl StartCondition {
+	// 下一次到达 hour:minute 的时间
+	pub fn next_time(hour: u32, minute: u32) -> Self {
+		let now = Local::now();
+		let mut target = now.with_hour(hour).and_then(|t| t.with_minute(minute))
+			.and_then(|t| t.with_second(0))
+			.unwrap_or(now);
+		if target <= now {
+			target += Duration::days(1);
+		}
+		Self::At(target.timestamp())
+	}
+
+	pub fn is_due(&self, idle: bool) -> bool {
+		match self {
+			Self::Now => true,
+			Self::At(time) => Local::now().timestamp() >= *time,
+			Self::WhenIdle => idle,
+			// 由 Session 检查磁盘空间
+			Self::WhenSpace => true,
+		}
+	}
+
+	pub fn get_countdown(&self) -> String {
+		match self {
+			Self::Now => String::new(),
+			Self::At(time) => {
+				let seconds = (*time - Local::now().timestamp()).max(0) as u64;
+				format!("Starts in {}", format_duration(std::time::Duration::from_secs(seconds)))
+			},
+			Self::WhenIdle => "Waiting for Idle".to_string(),
+			Self::WhenSpace => "Waiting for Disk Space".to_string(),
+		}
+	}
+
+	pub fn get_time(&self) -> String {
+		match self {
+			Self::Now => "Now".to_string(),
+			Self::At(time) => match Local.timestamp_opt(*time, 0).single() {
+				Some(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+				None => time.to_string(),
+			},
+			Self::WhenIdle => "When Idle".to_string(),
+			Self::WhenSpace => "When Disk Space Available".to_string(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledSession {
+	url: String,
+	webpage: String,
+	referrer: String,
+	cookie: Vec<Cookie>,
+	options: SessionOptions,
+	condition: StartCondition,
+	#[serde(default)]
+	category: String,
+}
+
+impl ScheduledSession {
+	pub fn new(session: &Session) -> Self {
+		Self {
+			url: session.get_url(),
+			webpage: session.get_webpage(),
+			referrer: session.get_referer(),
+			cookie: session.get_cookies(),
+			// 不把凭据明文保存到文件中
+			options: session.get_options().without_secrets(),
+			condition: session.get_start_condition(),
+			category: session.get_category(),
+		}
+	}
+
+	pub fn to_session(&self, uid: String, client: Option<Client>) -> Option<Session> {
+		let mut session = Session::new(self.url.clone(), client).ok()?;
+		session.set_uid(uid);
+		session.set_webpage(self.webpage.clone());
+		session.set_referer(self.referrer.clone());
+		session.set_cookie(self.cookie.clone());
+		session.set_options(self.options.clone());
+		session.set_start_condition(self.condition.clone());
+		session.set_category(self.category.clone());
+		Some(session)
+	}
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Scheduler {
+	sessions: HashMap<String, ScheduledSession>
+}
+
+impl Scheduler {
+	pub fn new() -> Self {
+		Self::from_file().unwrap_or_default()
+	}
+
+	pub fn from_file() -> Option<Self> {
+		let path = get_scheduled_path();
+		if path.exists() {
+			let contents = std::fs::read_to_string(path).ok()?;
+			serde_json::from_str(&contents).ok()
+		} else {
+			None
+		}
+	}
+
+	pub fn save(&self) {
+		let path = get_scheduled_path();
+		let result = serde_json::to_string_pretty(self)
+			.map_err(|e| e.to_string())
+			.and_then(|contents| {
+				if let Some(parent) = path.parent() {
+					std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+				}
+				std::fs::write(&path, contents).map_err(|e| e.to_string())
+			});
+		// 保存失败时不影响正在运行的任务
+		if let Err(msg) = result {
+			set_status_error(format!("Failed to save scheduled sessions to `{}`: {}", path.display(), msg));
+		}
+	}
+
+	pub fn add_session(&mut self, session: &Session) {
+		self.sessions.insert(session.get_uid(), ScheduledSession::new(session));
+		self.save();
+	}
+
+	pub fn get_sessions(&self, client: Option<Client>) -> Vec<Session> {
+		self.sessions.iter()
+			.filter_map(|(uid, s)| s.to_session(uid.clone(), client.clone()))
+			.collect()
+	}
+
+	pub fn remove(&mut self, uid: &String) {
+		if self.sessions.contains_key(uid) {
+			self.sessions.remove(uid);
+			self.save();
+		}
+	}
+}
@@ -1,7 +1,7 @@
 use actix_web::{HttpServer, App, web};
 use serde::{Serialize, Deserialize};
 
-use crate::data::{add_wait_to_start, set_focus_request, set_quit_request};
+use crate::{data::{add_wait_to_start, get_global_status, get_session_entries, set_focus_request, set_quit_request}, export::{self, ExportEntry, ExportFormat}, history::History, options::SessionOptions, scheduler::StartCondition};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -13,6 +13,12 @@ pub struct Info {
 	pub resume_state: bool,
 	pub download_cookie: Vec<Cookie>,
 	pub download_referer: String,
+	#[serde(default)]
+	pub options: SessionOptions,
+	#[serde(default)]
+	pub start_condition: StartCondition,
+	#[serde(default)]
+	pub mime: String,
 }
 
 impl Info {
@@ -25,6 +31,9 @@ impl Info {
 			resume_state: false,
 			download_cookie: vec![],
 			download_referer: "".to_string(),
+			options: SessionOptions::default(),
+			start_condition: StartCondition::Now,
+			mime: "".to_string(),
 		}
 	}
 }
@@ -32,16 +41,18 @@ impl Info {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Cookie {
-	domain: String,
-	host_only: bool,
-	http_only: bool,
-	name: String,
-	path: String,
-	same_site: String,
-	secure: bool,
-	session: bool,
-	store_id: String,
-	value: String,
+	pub domain: String,
+	pub host_only: bool,
+	pub http_only: bool,
+	pub name: String,
+	pub path: String,
+	pub same_site: String,
+	pub secure: bool,
+	pub session: bool,
+	pub store_id: String,
+	pub value: String,
+	#[serde(default)]
+	pub expiration_date: Option<f64>,
 }
 
 impl ToString for Cookie {
@@ -51,8 +62,11 @@ impl ToString for Cookie {
 }
 
 async fn index(info: web::Json<Info>) -> actix_web::Result<String> {
-	println!("{:?}", &info);
-	add_wait_to_start(info.clone());
+	log::info!("Receive download `{}` from `{}` with {} cookies", info.download_url, info.webpage_url, info.download_cookie.len());
+	log::debug!("{:?}", &info.options);
+	let mut info = info.into_inner();
+	info.options.restrict_for_api();
+	add_wait_to_start(info);
 	Ok("{}".to_string())
 }
 
@@ -60,26 +74,67 @@ async fn state() -> actix_web::Result<String> {
     Ok("{\"status\": 0}".to_string())
 }
 
+async fn stat() -> actix_web::Result<String> {
+	Ok(serde_json::to_string(&get_global_status().unwrap_or_default())?)
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+	format: Option<String>,
+	// sessions、history 或 all
+	source: Option<String>,
+}
+
+async fn export_handler(query: web::Query<ExportQuery>) -> actix_web::Result<String> {
+	let format = match &query.format {
+		Some(name) => ExportFormat::from_name(name)
+			.ok_or_else(|| actix_web::error::ErrorBadRequest(format!("Unknown format `{}`", name)))?,
+		None => ExportFormat::Json,
+	};
+	let source = query.source.clone().unwrap_or("all".to_string());
+	let entries = export::collect_entries(
+		&get_session_entries(),
+		&History::new(),
+		source == "sessions" || source == "all",
+		source == "history" || source == "all",
+	);
+	log::info!("Export {} entries as {}", entries.len(), format.get_name());
+	Ok(export::export(&entries, format))
+}
+
+async fn import_handler(entries: web::Json<Vec<ExportEntry>>) -> actix_web::Result<String> {
+	log::info!("Import {} entries", entries.len());
+	for entry in entries.iter().filter(|e| !e.url.trim().is_empty()) {
+		let mut info = entry.to_info();
+		info.options.restrict_for_api();
+		add_wait_to_start(info);
+	}
+	Ok("{\"status\": 0}".to_string())
+}
+
 async fn quit_handler() -> actix_web::Result<String> {
-	println!("Request Quit");
+	log::info!("Request Quit");
 	set_quit_request(true);
     Ok("{\"status\": 0}".to_string())
 }
 
 async fn focus() -> actix_web::Result<String> {
-	println!("Request Focus");
+	log::info!("Request Focus");
 	set_focus_request(true);
 	Ok("{\"status\": 0}".to_string())
 }
 
 pub async fn listen() {
-	println!("Start Server");
+	log::info!("Start Server");
 	HttpServer::new(|| {
 		App::new()
 			.route("/api", web::post().to(index))
 			.route("/state", web::get().to(state))
+			.route("/stat", web::get().to(stat))
 			.route("/focus", web::get().to(focus))
 			.route("/quit", web::get().to(quit_handler))
+			.route("/export", web::get().to(export_handler))
+			.route("/import", web::post().to(import_handler))
 	})
 	.bind("127.0.0.1:63318").unwrap()
 	.run().await
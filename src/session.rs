@@ -1,18 +1,13 @@
-use std::{path::Path, time::Instant, process::Command};
+use std::{path::{Path, PathBuf}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}, process::Command};
 
-use aria2_ws::{response::Status, Client};
+use aria2_ws::{response::{Status, TaskStatus}, Client};
 use url::Url;
 use uuid::Uuid;
 
-use crate::{aria2c, data::set_status_info, server::Cookie};
+use crate::{actions::{self, ActionResult}, aria2c::{self, QueueMove}, cookies::{filter_cookies, remove_task_cookies}, data::{get_settings, set_status_error, set_status_info, set_status_warning}, error::DownloadError, format::{format_duration, format_percent, format_size, format_speed}, notification::{self, NotificationMode, NotifyState}, options::SessionOptions, probe::{self, fallback_name, sanitize_filename, ProbeHandle}, retry::{RetryAttempt, RetryPolicy}, scheduler::StartCondition, server::Cookie, settings::get_download_dir, stats::{get_available_space, SpeedHistory}};
 
-const UNITS: [&str; 5] = [
-	"B/s",
-	"KB/s",
-	"MB/s",
-	"GB/s",
-	"TB/s"
-];
+// 平滑速度时新样本所占的权重
+const SPEED_SMOOTHING: f64 = 0.3;
 
 #[derive(Clone)]
 pub struct Session {
@@ -25,8 +20,26 @@ pub struct Session {
 	update_frequency: u128,
 	running: bool,
 	name: String,
+	size: Option<u64>,
+	mime: String,
+	probe: Option<ProbeHandle>,
 	cookie: Vec<Cookie>,
+	redirect_hosts: Vec<String>,
+	cookie_file: Option<PathBuf>,
 	referrer: String,
+	options: SessionOptions,
+	start_condition: StartCondition,
+	retry_attempts: Vec<RetryAttempt>,
+	next_retry: Option<Instant>,
+	smoothed_speed: f64,
+	active_time: Duration,
+	last_status_time: Option<Instant>,
+	start_completed: Option<u64>,
+	speed_history: SpeedHistory,
+	notify_state: NotifyState,
+	post_actions_started: bool,
+	action_results: Arc<Mutex<Vec<ActionResult>>>,
+	category: String,
 	client: Option<Client>,
 }
 
@@ -35,19 +48,11 @@ impl Session {
 		let parsed_url = match Url::parse(&url) {
 			Ok(u) => u,
 			Err(_) => {
-				set_status_info(format!("Invalid Url `{}`", &url));
+				set_status_error(format!("Invalid Url `{}`", &url));
 				return Err(());
 			}
 		};
-		let segments = match parsed_url.path_segments() {
-			Some(s) => s,
-			_ => {
-				set_status_info(format!("Failed to Solve Url `{}`", &url));
-				return Err(());
-			}
-		};
-
-		let name = segments.last().unwrap();
+		let name = fallback_name(&parsed_url);
 
 		Ok(Self {
 			uid: Uuid::new_v4().to_string(),
@@ -58,9 +63,27 @@ impl Session {
 			update_time: Instant::now(),
 			update_frequency: 100,
 			running: false,
-			name: name.to_string(),
+			name,
+			size: None,
+			mime: String::new(),
+			probe: None,
 			cookie: vec![],
+			redirect_hosts: vec![],
+			cookie_file: None,
 			referrer: String::new(),
+			options: SessionOptions::default(),
+			start_condition: StartCondition::Now,
+			retry_attempts: vec![],
+			next_retry: None,
+			smoothed_speed: 0.0,
+			active_time: Duration::ZERO,
+			last_status_time: None,
+			start_completed: None,
+			speed_history: SpeedHistory::default(),
+			notify_state: NotifyState::Running,
+			post_actions_started: false,
+			action_results: Arc::new(Mutex::new(vec![])),
+			category: String::new(),
 			client,
 		})
 	}
@@ -69,10 +92,39 @@ impl Session {
 		self.uid.clone()
 	}
 
+	pub fn set_uid(&mut self, uid: String) {
+		self.uid = uid;
+	}
+
 	pub fn get_url(&self) -> String {
 		self.url.clone()
 	}
 
+	pub fn get_host(&self) -> String {
+		Url::parse(&self.url).ok()
+			.and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+			.unwrap_or_default()
+	}
+
+	// 下载完成后文件所在的位置
+	pub fn get_target_path(&self) -> PathBuf {
+		let file = self.get_file();
+		if !file.is_empty() {
+			return PathBuf::from(file);
+		}
+		let dir = if self.options.dir.trim().is_empty() {
+			get_download_dir()
+		} else {
+			PathBuf::from(self.options.dir.trim())
+		};
+		let name = if self.options.out.trim().is_empty() {
+			self.name.clone()
+		} else {
+			sanitize_filename(&self.options.out)
+		};
+		dir.join(name)
+	}
+
 	pub fn get_file(&self) -> String {
 		if !self.status.is_none() {
 			self.status.clone().unwrap().files[0].path.clone()
@@ -103,6 +155,67 @@ impl Session {
 		result
 	}
 
+	pub fn get_size(&self) -> Option<u64> {
+		match &self.status {
+			Some(status) if status.total_length > 0 => Some(status.total_length),
+			_ => self.size,
+		}
+	}
+
+	pub fn set_size(&mut self, size: Option<u64>) {
+		self.size = size;
+	}
+
+	pub fn get_mime(&self) -> String {
+		self.mime.clone()
+	}
+
+	pub fn set_mime(&mut self, mime: String) {
+		self.mime = mime;
+	}
+
+	// 开始前在后台获取文件名、大小以及 MIME 类型，返回是否开始探测
+	pub fn start_probe(&mut self) -> bool {
+		if !probe::can_probe(&self.url) {
+			return false;
+		}
+		let session = self.clone();
+		self.probe = Some(ProbeHandle::spawn(self.url.clone(), move || aria2c::get_probe_options(&session)));
+		true
+	}
+
+	// 返回探测是否已经结束
+	pub fn poll_probe(&mut self) -> bool {
+		let result = match &self.probe {
+			Some(handle) => match handle.try_take() {
+				Some(result) => result,
+				None => return false,
+			},
+			None => return true,
+		};
+		self.probe = None;
+		if let Some(result) = result {
+			if let Some(name) = result.disposition_name {
+				// 使用服务器指定的文件名保存，手动指定的优先
+				if self.options.out.trim().is_empty() {
+					self.options.out = name.clone();
+				}
+				self.name = name;
+			} else if let Some(name) = result.url_name {
+				// 不指定 out，让 aria2 仍然可以使用 GET 响应中的文件名
+				self.name = name;
+			}
+			if result.size.is_some() {
+				self.size = result.size;
+			}
+			if !result.mime.is_empty() {
+				self.mime = result.mime;
+			}
+			self.redirect_hosts = result.redirect_hosts;
+		}
+		true
+	}
+
 	pub fn get_process(&self) -> f32 {
 		if !self.gid.is_empty() && !self.status.is_none() {
 			let status = self.status.clone().unwrap();
@@ -123,21 +236,68 @@ impl Session {
 		if self.get_verified_length() != 0 && !self.is_verified() {
 			return "Verifying...".to_string();
 		}
+		if self.is_deferred() {
+			return self.start_condition.get_countdown();
+		}
+		if self.is_queued() {
+			return "Queued".to_string();
+		}
+		if let Some(next_retry) = self.next_retry {
+			let seconds = next_retry.saturating_duration_since(Instant::now()).as_secs();
+			return format!("Retry in {}s ({})", seconds, self.retry_attempts.len() + 1);
+		}
+		if let Some(error) = self.get_error() {
+			return format!("Failed: {}", error.get_description());
+		}
 		if !self.gid.is_empty() && !self.status.is_none() && self.running {
 			let speed = self.status.clone().unwrap().download_speed;
-			let mut result_speed = speed as f32;
-			let mut unit_index = 0;
-			while result_speed > 1024.0 && unit_index < UNITS.len() {
-				result_speed /= 1024.0;
-				unit_index += 1;
-			}
-			result_speed.to_string() + UNITS[unit_index]
+			format!("{} | ETA {}", format_speed(speed), self.get_eta_data())
 		} else {
-			"0.0B/s".to_string()
+			format_speed(0)
+		}
+	}
+
+	pub fn get_speed_history(&self) -> &SpeedHistory {
+		&self.speed_history
+	}
+
+	pub fn get_eta(&self) -> Option<Duration> {
+		let status = self.status.as_ref()?;
+		if self.smoothed_speed < 1.0 || status.total_length == 0 {
+			return None;
+		}
+		let remaining = status.total_length.saturating_sub(status.completed_length);
+		Some(Duration::from_secs_f64(remaining as f64 / self.smoothed_speed))
+	}
+
+	pub fn get_eta_data(&self) -> String {
+		match self.get_eta() {
+			Some(eta) => format_duration(eta),
+			None => "--:--:--".to_string(),
+		}
+	}
+
+	// 只计算实际下载的时间
+	pub fn get_elapsed(&self) -> Duration {
+		self.active_time
+	}
+
+	pub fn get_average_speed(&self) -> u64 {
+		let elapsed = self.active_time.as_secs_f64();
+		if elapsed < 1.0 {
+			return 0;
 		}
+		let completed = self.status.as_ref().map(|s| s.completed_length).unwrap_or(0);
+		let downloaded = completed.saturating_sub(self.start_completed.unwrap_or(0));
+		(downloaded as f64 / elapsed) as u64
 	}
 
 	pub fn start(&mut self) {
+		// 失败的任务无法继续，需要重新提交
+		if self.is_error() {
+			self.restart();
+			return;
+		}
 		if !self.running {
 			if self.gid.is_empty() {
 				aria2c::add_uri(&self.client.clone(), self.url.clone(), self);
@@ -154,9 +314,134 @@ impl Session {
 		set_status_info(format!("Start `{}`", self.get_name()));
 	}
 
+	// 用户手动重新开始，重新计算自动重试的次数
+	pub fn restart(&mut self) {
+		self.retry_attempts.clear();
+		self.resubmit();
+		set_status_info(format!("Restart `{}`", self.get_name()));
+	}
+
+	fn retry(&mut self) {
+		let error = match self.get_error() {
+			Some(e) => e.to_string(),
+			None => self.get_error_code(),
+		};
+		self.retry_attempts.push(RetryAttempt::new(error, self.get_error_msg()));
+		self.resubmit();
+		set_status_info(format!("Retry `{}` ({})", self.get_name(), self.retry_attempts.len()));
+	}
+
+	fn resubmit(&mut self) {
+		aria2c::remove_download_result(&self.client, self.gid.clone());
+		self.gid = String::new();
+		self.status = None;
+		self.running = false;
+		self.next_retry = None;
+		aria2c::add_uri(&self.client.clone(), self.url.clone(), self);
+	}
+
+	pub fn check_retry(&mut self, policy: &RetryPolicy) {
+		if !self.is_error() {
+			self.next_retry = None;
+			return;
+		}
+		match self.next_retry {
+			Some(next_retry) => {
+				if Instant::now() >= next_retry {
+					self.retry();
+				}
+			},
+			None => {
+				if policy.should_retry(&self.get_error_code(), self.retry_attempts.len()) {
+					self.next_retry = Some(Instant::now() + policy.get_delay(self.retry_attempts.len()));
+				}
+			}
+		}
+	}
+
+	// 只在状态变为完成或者最终失败时通知一次
+	pub fn check_notification(&mut self, mode: NotificationMode) {
+		let state = if self.is_completed() {
+			NotifyState::Completed
+		} else if self.is_error() && self.next_retry.is_none() {
+			NotifyState::Failed
+		} else {
+			NotifyState::Running
+		};
+		if state == self.notify_state {
+			return;
+		}
+		self.notify_state = state;
+		let dir = self.status.as_ref().map(|s| s.dir.clone()).unwrap_or_default();
+		match (state, mode) {
+			(NotifyState::Completed, NotificationMode::All) => notification::notify(
+				"Download Completed".to_string(),
+				self.get_name(),
+				self.get_file(),
+				dir,
+			),
+			(NotifyState::Failed, NotificationMode::All | NotificationMode::FailuresOnly) => {
+				let reason = match self.get_error() {
+					Some(e) => e.get_description(),
+					None => self.get_error_msg(),
+				};
+				notification::notify(
+					"Download Failed".to_string(),
+					format!("{}: {}", self.get_name(), reason),
+					String::new(),
+					dir,
+				);
+			},
+			_ => {}
+		}
+	}
+
+	// 完成后在后台依次执行完成后操作
+	pub fn check_post_actions(&mut self) {
+		if self.post_actions_started || !self.is_completed() || self.options.post_actions.is_empty() {
+			return;
+		}
+		self.post_actions_started = true;
+		let post_actions = self.options.post_actions.clone();
+		let (file, url, name) = (self.get_file(), self.get_url(), self.get_name());
+		let action_results = self.action_results.clone();
+		set_status_info(format!("Run post actions of `{}`", name));
+		thread::spawn(move || {
+			let results = actions::run_actions(&post_actions, &file, &url, &name);
+			for result in results.iter() {
+				if result.success {
+					log::info!("Post action of `{}`: {}", name, result);
+				} else {
+					log::warn!("Post action of `{}`: {}", name, result);
+				}
+			}
+			*action_results.lock().unwrap() = results;
+		});
+	}
+
+	// aria2 只在开始时读取 cookie 文件，完成后即可删除
+	pub fn check_cookie_file(&mut self) {
+		if self.is_completed() {
+			self.set_cookie_file(None);
+		}
+	}
+
+	pub fn get_action_results(&self) -> Vec<ActionResult> {
+		self.action_results.lock().unwrap().clone()
+	}
+
+	pub fn is_retrying(&self) -> bool {
+		!self.retry_attempts.is_empty()
+	}
+
+	pub fn get_retry_attempts(&self) -> Vec<RetryAttempt> {
+		self.retry_attempts.clone()
+	}
+
 	pub fn remove(&mut self) {
 		aria2c::remove(&self.client, self.gid.clone());
 		self.running = false;
+		self.set_cookie_file(None);
 		set_status_info(format!("Remove `{}`", self.get_name()));
 	}
 
@@ -172,6 +457,12 @@ impl Session {
 		set_status_info(format!("Continue `{}`", self.get_name()));
 	}
 
+	pub fn move_in_queue(&self, queue_move: QueueMove) {
+		if !self.gid.is_empty() {
+			aria2c::change_position(&self.client, self.gid.clone(), queue_move);
+		}
+	}
+
 	pub fn update_status(&mut self) {
 		if !self.gid.is_empty() {
 			if self.update_time.elapsed().as_millis() > self.update_frequency {
@@ -182,6 +473,27 @@ impl Session {
 	}
 
 	pub fn update_status_handler(&mut self, new_status: Status) {
+		let now = Instant::now();
+		if new_status.status == TaskStatus::Active {
+			if let Some(last_status_time) = self.last_status_time {
+				self.active_time += now - last_status_time;
+			}
+			self.last_status_time = Some(now);
+			// 指数平滑，避免剩余时间跳动
+			let speed = new_status.download_speed as f64;
+			self.smoothed_speed = if self.smoothed_speed == 0.0 {
+				speed
+			} else {
+				SPEED_SMOOTHING * speed + (1.0 - SPEED_SMOOTHING) * self.smoothed_speed
+			};
+		} else {
+			self.last_status_time = None;
+			self.smoothed_speed = 0.0;
+		}
+		if self.start_completed.is_none() {
+			self.start_completed = Some(new_status.completed_length);
+		}
+		self.speed_history.push(new_status.download_speed);
 		self.status = Some(new_status);
 	}
 
@@ -217,25 +529,81 @@ impl Session {
 		}
 	}
 
+	pub fn set_start_condition(&mut self, condition: StartCondition) {
+		self.start_condition = condition;
+	}
+
+	pub fn get_start_condition(&self) -> StartCondition {
+		self.start_condition.clone()
+	}
+
+	// 等待计划时间或者空闲时才会交给 aria2
+	pub fn is_deferred(&self) -> bool {
+		self.gid.is_empty() && self.start_condition != StartCondition::Now
+	}
+
+	pub fn should_start(&self, idle: bool) -> bool {
+		self.is_deferred() && self.client.is_some() && self.start_condition.is_due(idle)
+		&& (self.start_condition != StartCondition::WhenSpace || self.has_enough_space(get_settings().get_min_free_space()))
+	}
+
+	// 剩余部分下载后仍保留 reserve 字节的空间，大小未知时视为足够
+	pub fn has_enough_space(&self, reserve: u64) -> bool {
+		let size = match self.get_size() {
+			Some(size) => size,
+			None => return true,
+		};
+		let completed = self.status.as_ref().map(|s| s.completed_length).unwrap_or(0);
+		let target = self.get_target_path();
+		match target.parent().and_then(get_available_space) {
+			Some(free) => free >= size.saturating_sub(completed) + reserve,
+			None => true,
+		}
+	}
+
+	pub fn is_active(&self) -> bool {
+		if let Some(status) = &self.status {
+			!self.gid.is_empty() && status.status == TaskStatus::Active
+		} else {
+			false
+		}
+	}
+
+	pub fn is_queued(&self) -> bool {
+		if let Some(status) = &self.status {
+			!self.gid.is_empty() && status.status == TaskStatus::Waiting
+		} else {
+			false
+		}
+	}
+
+	pub fn is_paused(&self) -> bool {
+		if let Some(status) = &self.status {
+			!self.gid.is_empty() && status.status == TaskStatus::Paused
+		} else {
+			false
+		}
+	}
+
 	pub fn is_error(&self) -> bool {
-		if let Some(status) = self.status.clone() {
-			if status.error_code.is_none() {
-				false
-			} else {
-				if status.error_code.unwrap() == "0" {
-					true
-				} else {
-					false
-				}
-			}
+		if let Some(status) = &self.status {
+			!self.gid.is_empty() && status.status == TaskStatus::Error
 		} else {
 			false
 		}
 	}
 
+	pub fn get_error(&self) -> Option<DownloadError> {
+		if self.is_error() {
+			DownloadError::from_code(&self.get_error_code())
+		} else {
+			None
+		}
+	}
+
 	pub fn open(&self) {
 		if self.status.is_none() {
-			set_status_info("This session has not started".to_string());
+			set_status_warning("This session has not started".to_string());
 		}
 		let mut command = Command::new("open");
 		command.arg(&self.status.clone().unwrap().files[0].path);
@@ -244,7 +612,7 @@ impl Session {
 
 	pub fn open_folder(&self) {
 		if self.status.is_none() {
-			set_status_info("This session has not started".to_string());
+			set_status_warning("This session has not started".to_string());
 		}
 		let mut command = Command::new("open");
 		command.arg(&self.status.clone().unwrap().dir);
@@ -255,8 +623,38 @@ impl Session {
 		self.cookie = cookie;
 	}
 
+	pub fn get_cookies(&self) -> Vec<Cookie> {
+		self.cookie.clone()
+	}
+
+	// 下载地址以及重定向经过的 host
+	pub fn get_cookie_hosts(&self) -> Vec<String> {
+		let mut hosts = vec![self.get_host()];
+		for host in self.redirect_hosts.iter() {
+			if !hosts.contains(host) {
+				hosts.push(host.clone());
+			}
+		}
+		hosts
+	}
+
+	// 替换任务的 cookie 文件，旧的文件会被删除
+	pub fn set_cookie_file(&mut self, path: Option<PathBuf>) {
+		if let Some(old) = self.cookie_file.take() {
+			if Some(&old) != path.as_ref() {
+				remove_task_cookies(&old);
+			}
+		}
+		self.cookie_file = path;
+	}
+
+	pub fn has_cookie_file(&self) -> bool {
+		self.cookie_file.is_some()
+	}
+
+	// 只包含可以发送到下载地址的 cookie
 	pub fn get_cookie(&self) -> String {
-		self.cookie.iter().map(|c| c.to_string()).collect::<Vec<String>>().join("; ")
+		filter_cookies(&self.cookie, &self.url).iter().map(|c| c.to_string()).collect::<Vec<String>>().join("; ")
 	}
 
 	pub fn set_referer(&mut self, referrer: String) {
@@ -275,6 +673,29 @@ impl Session {
 		self.webpage.clone()
 	}
 
+	pub fn set_options(&mut self, options: SessionOptions) {
+		self.options = options;
+	}
+
+	pub fn get_options(&self) -> SessionOptions {
+		self.options.clone()
+	}
+
+	pub fn change_options(&mut self, options: SessionOptions) {
+		self.options = options;
+		if !self.gid.is_empty() {
+			aria2c::change_option(&self.client.clone(), self.gid.clone(), self);
+		}
+	}
+
+	pub fn set_category(&mut self, category: String) {
+		self.category = category;
+	}
+
+	pub fn get_category(&self) -> String {
+		self.category.clone()
+	}
+
 	pub fn get_gid(&self) -> String {
 		self.gid.clone()
 	}
@@ -284,9 +705,9 @@ impl Session {
 			String::new()
 		} else {
 			let status = self.status.clone().unwrap();
-			format!("{}% ( {} / {} )",
-				status.completed_length as f32 / status.total_length as f32 * 100.0,
-				status.completed_length, status.total_length,
+			format!("{} ( {} / {} )",
+				format_percent(status.completed_length, status.total_length),
+				format_size(status.completed_length), format_size(status.total_length),
 			)
 		}
 	}
@@ -296,9 +717,9 @@ impl Session {
 			String::new()
 		} else {
 			let status = self.status.clone().unwrap();
-			format!("{}% ( {} / {} )",
-				self.get_verified_length() as f32 / status.total_length as f32 * 100.0,
-				self.get_verified_length(), status.total_length,
+			format!("{} ( {} / {} )",
+				format_percent(self.get_verified_length(), status.total_length),
+				format_size(self.get_verified_length()), format_size(status.total_length),
 			)
 		}
 	}
@@ -319,6 +740,27 @@ impl Session {
 		}
 	}
 
+	// bitfield 的最高位对应第 0 块
+	pub fn get_pieces(&self) -> Vec<bool> {
+		let status = match &self.status {
+			Some(s) => s,
+			None => return vec![],
+		};
+		let bitfield = match &status.bitfield {
+			Some(b) => b,
+			None => return vec![],
+		};
+		let mut pieces = vec![];
+		for c in bitfield.chars() {
+			let value = c.to_digit(16).unwrap_or(0);
+			for bit in (0..4).rev() {
+				pieces.push(value & (1 << bit) != 0);
+			}
+		}
+		pieces.truncate(status.num_pieces as usize);
+		pieces
+	}
+
 	pub fn get_pieces_length(&self) -> u64 {
 		if self.status.is_none() {
 			0
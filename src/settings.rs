@@ -3,6 +3,8 @@ use std::{path::PathBuf, fs};
 use serde::{Serialize, Deserialize};
 use users::os::unix::UserExt;
 
+use crate::{bandwidth::BandwidthRule, category::{get_default_categories, Category}, credentials::Credential, duplicate::DuplicatePolicy, notification::NotificationMode, proxy::ProxyMode, retry::RetryPolicy, site_rules::SiteRule};
+
 const BUNDLE_ID: &str = "com.iewnfod.ariadownloadmanager";
 const SETTINGS_FILE: &str = "settings.json";
 
@@ -14,19 +16,49 @@ pub fn get_app_support_path() -> PathBuf {
 		.join(BUNDLE_ID)
 }
 
+pub fn get_download_dir() -> PathBuf {
+	let user = users::get_user_by_uid(users::get_current_uid()).unwrap();
+	user.home_dir().join("Downloads")
+}
+
 fn get_save_path() -> PathBuf {
 	get_app_support_path()
 		.join(SETTINGS_FILE)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
 	pub split_num: i32,
+	pub max_concurrent_downloads: i32,
+	pub proxy_mode: ProxyMode,
 	pub proxy: String,
+	pub no_proxy: String,
+	pub pac_url: String,
 	pub user_agent: String,
 	pub custom_theme: bool,
 	pub dark_mode: bool,
 	pub close_after_seconds: u64,
+	pub max_download_limit: String,
+	pub max_upload_limit: String,
+	pub enable_bandwidth_schedule: bool,
+	pub bandwidth_schedule: Vec<BandwidthRule>,
+	pub retry_policy: RetryPolicy,
+	pub persist_status_log: bool,
+	pub log_filter: String,
+	pub notification_mode: NotificationMode,
+	pub categories: Vec<Category>,
+	// 分类没有设置文件夹时是否保存到以分类命名的子文件夹
+	pub category_subfolders: bool,
+	pub site_rules: Vec<SiteRule>,
+	pub credentials: Vec<Credential>,
+	pub probe_file_info: bool,
+	pub confirm_new_downloads: bool,
+	pub skip_confirm_hosts: Vec<String>,
+	pub duplicate_policy: DuplicatePolicy,
+	// 单位为 MB，0 表示不检查
+	pub min_free_space: u64,
+	pub queue_when_low_space: bool,
 	save_path: PathBuf,
 }
 
@@ -35,24 +67,50 @@ impl Default for Settings {
 		let save_path: PathBuf = get_save_path();
 		Self {
 			split_num: 16,
+			max_concurrent_downloads: 5,
+			proxy_mode: ProxyMode::Manual,
 			proxy: "".to_string(),
+			no_proxy: "".to_string(),
+			pac_url: "".to_string(),
 			user_agent: "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36".to_string(),
 			custom_theme: false,
 			dark_mode: false,
 			close_after_seconds: 0,
+			max_download_limit: "".to_string(),
+			max_upload_limit: "".to_string(),
+			enable_bandwidth_schedule: false,
+			bandwidth_schedule: vec![],
+			retry_policy: RetryPolicy::default(),
+			persist_status_log: false,
+			log_filter: "info".to_string(),
+			notification_mode: NotificationMode::All,
+			categories: get_default_categories(),
+			category_subfolders: false,
+			site_rules: vec![],
+			credentials: vec![],
+			probe_file_info: true,
+			confirm_new_downloads: false,
+			skip_confirm_hosts: vec![],
+			duplicate_policy: DuplicatePolicy::Ask,
+			min_free_space: 0,
+			queue_when_low_space: false,
 			save_path
 		}
 	}
 }
 
 impl Settings {
+	pub fn get_min_free_space(&self) -> u64 {
+		self.min_free_space * 1024 * 1024
+	}
+
 	pub fn new() -> Self {
 		let save_setting = Self::from_save();
 		if save_setting.is_none() {
-			println!("default settings");
+			log::info!("default settings");
 			Self::default()
 		} else {
-			println!("settings from save");
+			log::info!("settings from save");
 			save_setting.unwrap()
 		}
 	}
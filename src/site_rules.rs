@@ -0,0 +1,122 @@
+use aria2_ws::TaskOptions;
+use serde::{Serialize, Deserialize};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefererPolicy {
+	// 使用浏览器提供的 Referer
+	#[default]
+	Keep,
+	// 不发送 Referer
+	NoReferer,
+	// 只发送来源的 scheme 与 host
+	OriginOnly,
+}
+
+impl RefererPolicy {
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			Self::Keep => "Keep",
+			Self::NoReferer => "No Referer",
+			Self::OriginOnly => "Origin Only",
+		}
+	}
+
+	pub fn apply(&self, referer: &str) -> Option<String> {
+		match self {
+			Self::Keep => Some(referer.to_string()),
+			Self::NoReferer => None,
+			Self::OriginOnly => Url::parse(referer).ok()
+				.map(|u| u.origin().ascii_serialization() + "/"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteRule {
+	// 匹配 host 的通配符，例如 `*.example.com`
+	pub host: String,
+	pub user_agent: String,
+	// None 表示使用全局设置，空字符串表示不使用代理
+	pub proxy: Option<String>,
+	pub referer_policy: RefererPolicy,
+	pub headers: Vec<String>,
+	pub max_connection_per_server: Option<i32>,
+	pub max_download_limit: String,
+}
+
+// 支持 `*` 与 `?` 的通配符匹配
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	let (mut p, mut t) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+	while t < text.len() {
+		if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+			p += 1;
+			t += 1;
+		} else if p < pattern.len() && pattern[p] == '*' {
+			star = Some((p, t));
+			p += 1;
+		} else if let Some((star_p, star_t)) = star {
+			p = star_p + 1;
+			t = star_t + 1;
+			star = Some((star_p, star_t + 1));
+		} else {
+			return false;
+		}
+	}
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn set_header(headers: &mut Vec<String>, name: &str, value: Option<String>) {
+	let prefix = format!("{}:", name.to_lowercase());
+	headers.retain(|h| !h.to_lowercase().starts_with(&prefix));
+	if let Some(value) = value {
+		headers.push(format!("{}: {}", name, value));
+	}
+}
+
+impl SiteRule {
+	pub fn matches(&self, url: &str) -> bool {
+		let pattern = self.host.trim().to_lowercase();
+		if pattern.is_empty() {
+			return false;
+		}
+		match Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) {
+			Some(host) => glob_match(&pattern, &host),
+			None => false,
+		}
+	}
+
+	pub fn apply(&self, opt: &mut TaskOptions, referer: &str) {
+		let mut headers = opt.header.clone().unwrap_or_default();
+		if !self.user_agent.trim().is_empty() {
+			set_header(&mut headers, "User-Agent", Some(self.user_agent.trim().to_string()));
+		}
+		set_header(&mut headers, "Referer", self.referer_policy.apply(referer));
+		headers.extend(
+			self.headers.iter()
+				.map(|h| h.trim().to_string())
+				.filter(|h| !h.is_empty())
+		);
+		opt.header = Some(headers);
+		if let Some(proxy) = &self.proxy {
+			opt.all_proxy = Some(proxy.trim().to_string());
+		}
+		if let Some(max_connection_per_server) = self.max_connection_per_server {
+			opt.max_connection_per_server = Some(max_connection_per_server);
+		}
+		if !self.max_download_limit.trim().is_empty() {
+			opt.max_download_limit = Some(self.max_download_limit.trim().to_string());
+		}
+	}
+}
+
+// 第一条匹配的规则生效
+pub fn find_site_rule(rules: &[SiteRule], url: &str) -> Option<SiteRule> {
+	rules.iter()
+		.find(|r| r.matches(url))
+		.cloned()
+}
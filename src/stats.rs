@@ -0,0 +1,83 @@
+use std::{collections::VecDeque, ffi::CString, os::unix::ffi::OsStrExt, path::Path, time::{Duration, Instant}};
+
+use aria2_ws::response::GlobalStat;
+use serde::{Serialize, Deserialize};
+
+// 保留最近两分钟的速度
+const HISTORY_LENGTH: usize = 120;
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default)]
+pub struct SpeedHistory {
+	samples: VecDeque<u64>,
+	last_sample: Option<Instant>,
+}
+
+impl SpeedHistory {
+	pub fn push(&mut self, speed: u64) {
+		if let Some(last_sample) = self.last_sample {
+			if last_sample.elapsed() < SAMPLE_INTERVAL {
+				return;
+			}
+		}
+		self.last_sample = Some(Instant::now());
+		self.samples.push_back(speed);
+		while self.samples.len() > HISTORY_LENGTH {
+			self.samples.pop_front();
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.samples.is_empty()
+	}
+
+	// 最新的样本在 x = 0，越早的越靠左
+	pub fn get_points(&self) -> Vec<[f64; 2]> {
+		let len = self.samples.len();
+		self.samples.iter().enumerate()
+			.map(|(i, speed)| [i as f64 - (len as f64 - 1.0), *speed as f64])
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalStatus {
+	pub download_speed: u64,
+	pub upload_speed: u64,
+	pub num_active: i32,
+	pub num_waiting: i32,
+	pub num_stopped: i32,
+	pub download_dir: String,
+	pub free_space: Option<u64>,
+}
+
+impl GlobalStatus {
+	pub fn new(stat: &GlobalStat, download_dir: &Path) -> Self {
+		Self {
+			download_speed: stat.download_speed,
+			upload_speed: stat.upload_speed,
+			num_active: stat.num_active,
+			num_waiting: stat.num_waiting,
+			num_stopped: stat.num_stopped_total,
+			download_dir: download_dir.to_string_lossy().to_string(),
+			free_space: get_free_space(download_dir),
+		}
+	}
+}
+
+pub fn get_free_space(path: &Path) -> Option<u64> {
+	let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+	let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+	if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+		return None;
+	}
+	#[allow(clippy::unnecessary_cast)]
+	Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+// 目标文件夹可能还不存在，使用最近的已存在的上级文件夹
+pub fn get_available_space(path: &Path) -> Option<u64> {
+	let existing = path.ancestors().find(|p| p.exists())?;
+	get_free_space(existing)
+}
@@ -0,0 +1,79 @@
+use std::{fmt::Display, fs::{self, OpenOptions}, io::Write, path::{Path, PathBuf}};
+
+use chrono::Local;
+
+use crate::settings::get_app_support_path;
+
+const STATUS_LOG_FILE: &str = "status.log";
+// 单个日志文件最大 1MB，保留 3 个旧文件
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+const MAX_LOG_FILES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusLevel {
+	Info,
+	Warning,
+	Error,
+}
+
+impl Display for StatusLevel {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Info => write!(f, "INFO"),
+			Self::Warning => write!(f, "WARN"),
+			Self::Error => write!(f, "ERROR"),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusEntry {
+	pub time: String,
+	pub level: StatusLevel,
+	pub message: String,
+}
+
+impl StatusEntry {
+	pub fn new(level: StatusLevel, message: String) -> Self {
+		Self {
+			time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+			level,
+			message,
+		}
+	}
+}
+
+impl Display for StatusEntry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} [{}] {}", self.time, self.level, self.message)
+	}
+}
+
+pub fn get_status_log_path() -> PathBuf {
+	get_app_support_path()
+		.join(STATUS_LOG_FILE)
+}
+
+// 超过大小后依次重命名为 .1 .2 ...，最旧的会被覆盖
+pub fn append_with_rotation(path: &Path, line: &str) {
+	if let Some(parent) = path.parent() {
+		let _ = fs::create_dir_all(parent);
+	}
+	if let Ok(metadata) = fs::metadata(path) {
+		if metadata.len() > MAX_LOG_SIZE {
+			for i in (1..MAX_LOG_FILES).rev() {
+				let _ = fs::rename(rotated_path(path, i), rotated_path(path, i + 1));
+			}
+			let _ = fs::rename(path, rotated_path(path, 1));
+		}
+	}
+	if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+		let _ = writeln!(file, "{}", line);
+	}
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+	let mut name = path.as_os_str().to_os_string();
+	name.push(format!(".{}", index));
+	PathBuf::from(name)
+}
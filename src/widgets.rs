@@ -1,6 +1,6 @@
-use eframe::egui::Ui;
+use eframe::{egui::{ComboBox, DragValue, Grid, Sense, TextEdit, Ui, plot::{Legend, Line, Plot}}, epaint::{Color32, Rect, vec2}};
 
-use crate::data::CLICK_TO_COPY_PROMPT;
+use crate::{actions::PostAction, category::Category, data::CLICK_TO_COPY_PROMPT, format::format_speed, options::SessionOptions, site_rules::{RefererPolicy, SiteRule}, stats::SpeedHistory};
 
 pub fn click_copy_label<T>(ui: &mut Ui, text: T)
 where T: ToString
@@ -17,3 +17,267 @@ where T: ToString
 		});
 	}
 }
+
+pub fn optional_drag_value(ui: &mut Ui, value: &mut Option<i32>, default: i32, max: i32) {
+	ui.horizontal(|ui| {
+		let mut enabled = value.is_some();
+		ui.checkbox(&mut enabled, "");
+		if enabled {
+			let v = value.get_or_insert(default);
+			ui.add(DragValue::new(v).clamp_range(0..=max));
+		} else {
+			*value = None;
+			ui.label("(Global)");
+		}
+	});
+}
+
+pub fn session_options_grid(ui: &mut Ui, id: String, options: &mut SessionOptions) {
+	let id_source = id.clone();
+	Grid::new(id)
+	.num_columns(2)
+	.show(ui, |ui| {
+		ui.label("Connection Number");
+		optional_drag_value(ui, &mut options.split, 16, 64);
+		ui.end_row();
+
+		ui.label("Max Connection per Server");
+		optional_drag_value(ui, &mut options.max_connection_per_server, 1, 16);
+		ui.end_row();
+
+		ui.label("Max Download Limit");
+		ui.add(TextEdit::singleline(&mut options.max_download_limit).hint_text("e.g. 500K, 2M"));
+		ui.end_row();
+
+		ui.label("Proxy Url");
+		ui.add(TextEdit::singleline(&mut options.proxy).hint_text("(Global)"));
+		ui.end_row();
+
+		ui.label("Extra Headers");
+		let mut headers = options.headers.join("\n");
+		if ui.add(TextEdit::multiline(&mut headers).desired_rows(2).hint_text("Name: Value")).changed() {
+			options.headers = headers.split('\n').map(|h| h.to_string()).collect();
+		}
+		ui.end_row();
+
+		ui.label("Max Tries");
+		optional_drag_value(ui, &mut options.max_tries, 5, 100);
+		ui.end_row();
+
+		ui.label("Retry Wait (s)");
+		optional_drag_value(ui, &mut options.retry_wait, 0, 600);
+		ui.end_row();
+
+		ui.label("Download Folder");
+		ui.add(TextEdit::singleline(&mut options.dir).hint_text("(Global)"));
+		ui.end_row();
+
+		ui.label("File Name");
+		ui.add(TextEdit::singleline(&mut options.out).hint_text("(Auto)"));
+		ui.end_row();
+
+		ui.label("Post Actions");
+		post_actions_editor(ui, id_source.clone() + "actions", &mut options.post_actions);
+		ui.end_row();
+	});
+}
+
+// 直接保留原始输入，匹配时再去除空白
+pub fn comma_list_edit(ui: &mut Ui, list: &mut Vec<String>, hint: &str) {
+	let mut text = list.join(",");
+	if ui.add(TextEdit::singleline(&mut text).hint_text(hint)).changed() {
+		*list = text.split(',').map(|i| i.to_string()).collect();
+	}
+}
+
+pub fn category_editor(ui: &mut Ui, id: String, category: &mut Category) {
+	let id_source = id.clone();
+	Grid::new(id)
+	.num_columns(2)
+	.show(ui, |ui| {
+		ui.label("Name");
+		ui.text_edit_singleline(&mut category.name);
+		ui.end_row();
+
+		ui.label("Download Folder");
+		ui.add(TextEdit::singleline(&mut category.dir).hint_text("(Global)"));
+		ui.end_row();
+
+		ui.label("Extensions");
+		comma_list_edit(ui, &mut category.extensions, "e.g. zip,7z");
+		ui.end_row();
+
+		ui.label("MIME Types");
+		comma_list_edit(ui, &mut category.mime_types, "e.g. video/*");
+		ui.end_row();
+
+		ui.label("Domains");
+		comma_list_edit(ui, &mut category.domains, "e.g. example.com");
+		ui.end_row();
+
+		ui.label("Url Regex");
+		ui.text_edit_singleline(&mut category.url_regex);
+		ui.end_row();
+
+		ui.label("Post Actions");
+		post_actions_editor(ui, id_source + "actions", &mut category.post_actions);
+		ui.end_row();
+	});
+}
+
+pub fn post_actions_editor(ui: &mut Ui, id: String, actions: &mut Vec<PostAction>) {
+	ui.vertical(|ui| {
+		let mut wait_to_remove = None;
+		for (index, action) in actions.iter_mut().enumerate() {
+			ui.horizontal(|ui| {
+				let mut selected = action.get_name();
+				ComboBox::from_id_source(format!("{}{}", id, index))
+				.selected_text(selected)
+				.show_ui(ui, |ui| {
+					for a in PostAction::all() {
+						ui.selectable_value(&mut selected, a.get_name(), a.get_name());
+					}
+				});
+				// 切换类型时保留输入的内容
+				if selected != action.get_name() {
+					let value = action.get_value_mut().clone();
+					*action = match selected {
+						"Move" => PostAction::Move(value),
+						"Extract" => PostAction::Extract(value),
+						_ => PostAction::Command(value),
+					};
+				}
+				let hint = action.get_hint();
+				ui.add(TextEdit::singleline(action.get_value_mut()).hint_text(hint));
+				if ui.button("Remove").clicked() {
+					wait_to_remove = Some(index);
+				}
+			});
+		}
+		if let Some(index) = wait_to_remove {
+			actions.remove(index);
+		}
+		if ui.button("Add Action").clicked() {
+			actions.push(PostAction::Move(String::new()));
+		}
+	});
+}
+
+pub fn speed_graph(ui: &mut Ui, id: String, lines: Vec<(&str, &SpeedHistory)>, height: f32) {
+	let show_legend = lines.len() > 1;
+	let mut plot = Plot::new(id)
+		.height(height)
+		.include_y(0.0)
+		.include_x(0.0)
+		.show_x(false)
+		.allow_zoom(false)
+		.allow_drag(false)
+		.allow_scroll(false)
+		.allow_boxed_zoom(false)
+		.allow_double_click_reset(false)
+		.y_axis_formatter(|y, _range| format_speed(y.max(0.0) as u64))
+		.label_formatter(|name, point| format!("{} {}", name, format_speed(point.y.max(0.0) as u64)));
+	if show_legend {
+		plot = plot.legend(Legend::default());
+	}
+	plot.show(ui, |plot_ui| {
+		for (name, history) in lines {
+			plot_ui.line(Line::new(history.get_points()).name(name));
+		}
+	});
+}
+
+// 块太多时合并显示，颜色深浅表示完成的比例
+const PIECE_MAP_MAX_CELLS: usize = 512;
+const PIECE_MAP_MAX_COLUMNS: usize = 64;
+const PIECE_CELL_SIZE: f32 = 8.0;
+
+pub fn piece_map(ui: &mut Ui, pieces: &[bool]) {
+	if pieces.is_empty() {
+		ui.label("");
+		return;
+	}
+	let pieces_per_cell = pieces.len().div_ceil(PIECE_MAP_MAX_CELLS);
+	let cells: Vec<f32> = pieces.chunks(pieces_per_cell)
+		.map(|chunk| chunk.iter().filter(|p| **p).count() as f32 / chunk.len() as f32)
+		.collect();
+	let width = ui.available_width().clamp(PIECE_CELL_SIZE * 8.0, PIECE_CELL_SIZE * PIECE_MAP_MAX_COLUMNS as f32);
+	let columns = (width / PIECE_CELL_SIZE) as usize;
+	let rows = cells.len().div_ceil(columns);
+	let (rect, response) = ui.allocate_exact_size(
+		vec2(columns.min(cells.len()) as f32 * PIECE_CELL_SIZE, rows as f32 * PIECE_CELL_SIZE),
+		Sense::hover()
+	);
+	let painter = ui.painter_at(rect);
+	let completed_color = ui.visuals().selection.bg_fill;
+	let missing_color = ui.visuals().faint_bg_color;
+	for (index, fraction) in cells.iter().enumerate() {
+		let min = rect.min + vec2(
+			(index % columns) as f32 * PIECE_CELL_SIZE,
+			(index / columns) as f32 * PIECE_CELL_SIZE,
+		);
+		let cell = Rect::from_min_size(min, vec2(PIECE_CELL_SIZE - 1.0, PIECE_CELL_SIZE - 1.0));
+		painter.rect_filled(cell, 1.0, lerp_color(missing_color, completed_color, *fraction));
+	}
+	let completed = pieces.iter().filter(|p| **p).count();
+	response.on_hover_text(format!("{} / {} pieces", completed, pieces.len()));
+}
+
+fn lerp_color(from: Color32, to: Color32, t: f32) -> Color32 {
+	let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+	Color32::from_rgb(lerp(from.r(), to.r()), lerp(from.g(), to.g()), lerp(from.b(), to.b()))
+}
+
+pub fn site_rule_editor(ui: &mut Ui, id: String, rule: &mut SiteRule) {
+	let id_source = id.clone();
+	Grid::new(id)
+	.num_columns(2)
+	.show(ui, |ui| {
+		ui.label("Host");
+		ui.add(TextEdit::singleline(&mut rule.host).hint_text("e.g. *.example.com"));
+		ui.end_row();
+
+		ui.label("User Agent");
+		ui.add(TextEdit::singleline(&mut rule.user_agent).hint_text("(Global)"));
+		ui.end_row();
+
+		ui.label("Proxy Url");
+		ui.horizontal(|ui| {
+			let mut enabled = rule.proxy.is_some();
+			ui.checkbox(&mut enabled, "");
+			if enabled {
+				let proxy = rule.proxy.get_or_insert(String::new());
+				ui.add(TextEdit::singleline(proxy).hint_text("(Direct)"));
+			} else {
+				rule.proxy = None;
+				ui.label("(Global)");
+			}
+		});
+		ui.end_row();
+
+		ui.label("Referer");
+		ComboBox::from_id_source(id_source + "referer")
+		.selected_text(rule.referer_policy.get_name())
+		.show_ui(ui, |ui| {
+			for policy in [RefererPolicy::Keep, RefererPolicy::NoReferer, RefererPolicy::OriginOnly] {
+				ui.selectable_value(&mut rule.referer_policy, policy, policy.get_name());
+			}
+		});
+		ui.end_row();
+
+		ui.label("Extra Headers");
+		let mut headers = rule.headers.join("\n");
+		if ui.add(TextEdit::multiline(&mut headers).desired_rows(2).hint_text("Name: Value")).changed() {
+			rule.headers = headers.split('\n').map(|h| h.to_string()).collect();
+		}
+		ui.end_row();
+
+		ui.label("Max Connection per Server");
+		optional_drag_value(ui, &mut rule.max_connection_per_server, 1, 16);
+		ui.end_row();
+
+		ui.label("Max Download Limit");
+		ui.add(TextEdit::singleline(&mut rule.max_download_limit).hint_text("e.g. 500K, 2M"));
+		ui.end_row();
+	});
+}